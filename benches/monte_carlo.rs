@@ -1,4 +1,4 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use fastrand::Rng;
 use solve2048::{Board, ExpectimaxPlayer, MonteCarloPlayer, Move, Player};
 
@@ -62,6 +62,19 @@ fn move_down_benchmark(c: &mut Criterion) {
     });
 }
 
+fn column_moves_benchmark(c: &mut Criterion) {
+    let mut rng = Rng::with_seed(7);
+    let boards: Vec<Board> = (0..1024).map(|_| Board::from_raw(rng.u64(..))).collect();
+    c.bench_function("board move up and down, 1024 boards", |b| {
+        b.iter(|| {
+            for &brd in &boards {
+                let (mut up, mut down) = black_box((brd, brd));
+                black_box((up.move_up(), down.move_down(), up, down));
+            }
+        })
+    });
+}
+
 fn random_move_benchmark(c: &mut Criterion) {
     let mut rng = Rng::new();
     c.bench_function("generate random move", |b| {
@@ -128,6 +141,7 @@ criterion_group!(
     move_right_benchmark,
     move_down_benchmark,
     move_up_benchmark,
+    column_moves_benchmark,
     random_move_benchmark,
     random_run_benchmark,
     expectimax_calculation,
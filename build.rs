@@ -0,0 +1,50 @@
+//! Tabulates, for every row of four nibbles, its moves and its expectimax
+//! heuristic, so the crate can load both as statics instead of computing them
+//! on first use.
+//!
+//! - `move_rows.bin`: per row, the XOR masks of sliding it left and right
+//!   (`u16` each) and the merge score (`u32`).
+//! - `heuristic_rows.bin`: per row, its heuristic score (`f32`).
+//!
+//! Everything is little-endian and indexed by the row's raw `u16`.
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[allow(dead_code)]
+#[path = "src/row.rs"]
+mod row;
+
+#[path = "src/heuristic_row.rs"]
+mod heuristic_row;
+
+use heuristic_row::HeuristicParams;
+use row::Row;
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/row.rs");
+    println!("cargo:rerun-if-changed=src/heuristic_row.rs");
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let out_dir = Path::new(&out_dir);
+
+    let mut moves = Vec::with_capacity(8 << 16);
+    let mut heuristic = Vec::with_capacity(4 << 16);
+    for i in 0..=u16::MAX {
+        let row = Row::from_raw(i);
+        let (score, left) = row.shift_left();
+        let (_, right) = row.shift_right();
+        moves.extend_from_slice(&(left.raw ^ i).to_le_bytes());
+        moves.extend_from_slice(&(right.raw ^ i).to_le_bytes());
+        moves.extend_from_slice(&score.to_le_bytes());
+
+        let mut line = [0; 4];
+        for (j, cell) in line.iter_mut().enumerate() {
+            *cell = row.get(j as u8);
+        }
+        heuristic.extend_from_slice(
+            &heuristic_row::compute_row_score(&line, &HeuristicParams::DEFAULT).to_le_bytes(),
+        );
+    }
+    fs::write(out_dir.join("move_rows.bin"), moves).unwrap();
+    fs::write(out_dir.join("heuristic_rows.bin"), heuristic).unwrap();
+}
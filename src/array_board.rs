@@ -0,0 +1,88 @@
+use crate::game_board::fmt_tiles;
+use crate::{Board, GameBoard};
+use std::fmt;
+
+/// An N x N board with a byte per cell. It has no size limit worth speaking
+/// of and tiles can grow past the 32768 that fits in a nibble, at the cost of
+/// speed.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct ArrayBoard<const N: usize> {
+    cells: [[u8; N]; N],
+}
+
+/// A 4x4 board that plays past 32768. `Board` stays the representation for
+/// search; this one referees long games exactly.
+pub type WideBoard = ArrayBoard<4>;
+pub type Board6 = ArrayBoard<6>;
+
+impl<const N: usize> Default for ArrayBoard<N> {
+    fn default() -> Self {
+        Self { cells: [[0; N]; N] }
+    }
+}
+
+impl<const N: usize> GameBoard for ArrayBoard<N> {
+    const SIZE: u8 = {
+        assert!(N * N <= u8::MAX as usize, "ArrayBoard holds at most 15x15");
+        N as u8
+    };
+    // the largest tile whose value still fits in a u32
+    const MAX_EXPONENT: u8 = 31;
+
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn at(&self, i: u8) -> u8 {
+        self.cells[i as usize / N][i as usize % N]
+    }
+
+    fn set(&mut self, i: u8, val: u8) {
+        self.cells[i as usize / N][i as usize % N] = val;
+    }
+}
+
+impl<const N: usize> fmt::Display for ArrayBoard<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_tiles(self, f)
+    }
+}
+
+impl From<Board> for WideBoard {
+    fn from(b: Board) -> Self {
+        let mut w = Self::new();
+        for i in 0..16 {
+            w.set(i, b.at(i));
+        }
+        w
+    }
+}
+
+impl WideBoard {
+    pub fn from_exponents(cells: [u8; 16]) -> Self {
+        let mut w = Self::new();
+        for (i, &c) in cells.iter().enumerate() {
+            w.set(i as u8, c);
+        }
+        w
+    }
+
+    /// The same position as a `Board`, if every tile fits in a nibble.
+    pub fn to_board(&self) -> Option<Board> {
+        if self.log_max_tile() > Board::MAX_EXPONENT {
+            return None;
+        }
+        Some(self.to_board_saturating())
+    }
+
+    /// The position as a `Board`, with tiles above 32768 shown as 32768. Good
+    /// enough for players to choose a move on; the move itself should still
+    /// be made here.
+    pub fn to_board_saturating(&self) -> Board {
+        let mut b = Board::new();
+        for i in 0..16 {
+            b.set(i, self.at(i).min(Board::MAX_EXPONENT));
+        }
+        b
+    }
+}
@@ -0,0 +1,54 @@
+//! The command-line front end:
+//!
+//! - `solve2048-cli engine` speaks the engine protocol on stdin and stdout,
+//!   see `solve2048::Engine`;
+//! - `solve2048-cli serve [addr] [weights]` answers HTTP requests for
+//!   evaluations, see `solve2048::SolverServer`, on `127.0.0.1:8048` unless
+//!   given an address, with the n-tuple network in `weights` if given.
+use solve2048::{Engine, NTuple, SolverServer, WeightFormat};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: solve2048-cli engine\n       solve2048-cli serve [addr] [weights]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args[..] {
+        ["engine"] => engine(),
+        ["serve", ref rest @ ..] if rest.len() <= 2 => serve(
+            rest.first().copied().unwrap_or("127.0.0.1:8048"),
+            rest.get(1).copied(),
+        ),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("solve2048-cli: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn engine() -> Result<(), Box<dyn Error>> {
+    let mut stdout = io::stdout().lock();
+    Engine::default().run(io::stdin().lock(), &mut stdout)?;
+    Ok(())
+}
+
+fn serve(addr: &str, weights: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let mut server = SolverServer::bind(addr)?;
+    if let Some(path) = weights {
+        let mut file = BufReader::new(File::open(path)?);
+        server = server.with_ntuple(NTuple::import(&mut file, &WeightFormat::native())?);
+    }
+    eprintln!("serving on http://{}", server.addr());
+    server.serve();
+    Ok(())
+}
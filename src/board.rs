@@ -1,33 +1,26 @@
-use crate::MoveRecord;
+use crate::game_board::fmt_tiles;
+pub use crate::row::Row;
+use crate::{row, GameBoard, MoveRecord, SpawnRules};
 use fastrand::Rng;
-use lazy_static::lazy_static;
+use std::error::Error;
 use std::fmt;
+use std::vec;
 
-lazy_static! {
-    static ref CACHE: MoveCache = MoveCache::new();
-}
+/// Tabulated at build time, so there's nothing to initialise at startup.
+static CACHE: MoveCache = MoveCache::new();
 
-#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub struct Board {
     pub raw: u64,
 }
 
 impl fmt::Display for Board {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut board_str = String::new();
-        for y in 0..4 {
-            for x in 0..4 {
-                let value = self.get(x, y);
-                board_str.push_str(&format!("{} ", if value == 0 { 0 } else { 1u32 << value }));
-            }
-            board_str.pop(); // Remove the last space
-            board_str.push('\n'); // Newline after each row
-        }
-        write!(f, "{}", board_str)
+        fmt_tiles(self, f)
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub enum Move {
     Up,
     Down,
@@ -50,7 +43,7 @@ impl Move {
         }
     }
 
-    pub fn to_int(&self) -> i32 {
+    pub const fn to_int(&self) -> i32 {
         match self {
             Move::Up => 0,
             Move::Right => 1,
@@ -65,7 +58,111 @@ impl Move {
     }
 }
 
+/// Why a list of cells doesn't describe a `Board`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoardError {
+    /// There weren't 16 cells.
+    Length(usize),
+    /// A cell held something that isn't a tile (or exponent) a `Board` holds.
+    Cell { cell: usize, value: i32 },
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoardError::Length(len) => write!(f, "expected 16 cells, got {len}"),
+            BoardError::Cell { cell, value } => write!(f, "cell {cell} can't hold {value}"),
+        }
+    }
+}
+
+impl Error for BoardError {}
+
+/// A set of moves, e.g. the legal moves from a position.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MoveSet {
+    bits: u8,
+}
+
+impl MoveSet {
+    pub const fn empty() -> Self {
+        Self { bits: 0 }
+    }
+
+    pub fn all() -> Self {
+        Move::all().into_iter().collect()
+    }
+
+    pub fn insert(&mut self, m: Move) {
+        self.bits |= 1 << m.to_int();
+    }
+
+    pub fn remove(&mut self, m: Move) {
+        self.bits &= !(1 << m.to_int());
+    }
+
+    pub const fn contains(&self, m: Move) -> bool {
+        self.bits & (1 << m.to_int()) != 0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    pub const fn len(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    /// The moves in the set, in the order of `Move::all`.
+    pub fn iter(&self) -> MoveSetIter {
+        MoveSetIter {
+            set: *self,
+            next: 0,
+        }
+    }
+}
+
+pub struct MoveSetIter {
+    set: MoveSet,
+    next: usize,
+}
+
+impl Iterator for MoveSetIter {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        while self.next < 4 {
+            let m = Move::all()[self.next];
+            self.next += 1;
+            if self.set.contains(m) {
+                return Some(m);
+            }
+        }
+        None
+    }
+}
+
+impl FromIterator<Move> for MoveSet {
+    fn from_iter<I: IntoIterator<Item = Move>>(moves: I) -> Self {
+        let mut set = Self::empty();
+        moves.into_iter().for_each(|m| set.insert(m));
+        set
+    }
+}
+
+impl IntoIterator for MoveSet {
+    type Item = Move;
+    type IntoIter = MoveSetIter;
+
+    fn into_iter(self) -> MoveSetIter {
+        self.iter()
+    }
+}
+
 impl Board {
+    /// Largest exponent a nibble holds (32768).
+    pub const MAX_EXPONENT: u8 = row::MAX_EXPONENT;
+
     pub fn new() -> Self {
         Self { raw: 0 }
     }
@@ -74,6 +171,9 @@ impl Board {
         Self { raw }
     }
 
+    /// Build a board from 16 exponents in reading order. Unchecked: values
+    /// are truncated to a nibble and a short slice panics; see
+    /// `try_from_exponents`.
     pub fn from_arr(arr: &[i32]) -> Self {
         let mut b = Board::new();
         for i in 0..4 {
@@ -85,13 +185,44 @@ impl Board {
         b
     }
 
+    /// Build a board from 16 exponents in reading order, 0 for empty.
+    pub fn try_from_exponents(cells: &[i32]) -> Result<Self, BoardError> {
+        Self::try_from_cells(cells, |e| {
+            (0..=Self::MAX_EXPONENT as i32)
+                .contains(&e)
+                .then_some(e as u8)
+        })
+    }
+
+    /// Build a board from 16 tile values in reading order, 0 for empty.
+    pub fn try_from_tiles(cells: &[i32]) -> Result<Self, BoardError> {
+        Self::try_from_cells(cells, |v| match v {
+            0 => Some(0),
+            v if v >= 2 && (v as u32).is_power_of_two() => {
+                let e = v.trailing_zeros() as u8;
+                (e <= Self::MAX_EXPONENT).then_some(e)
+            }
+            _ => None,
+        })
+    }
+
+    fn try_from_cells(
+        cells: &[i32],
+        exponent: impl Fn(i32) -> Option<u8>,
+    ) -> Result<Self, BoardError> {
+        if cells.len() != 16 {
+            return Err(BoardError::Length(cells.len()));
+        }
+        let mut b = Board::new();
+        for (i, &v) in cells.iter().enumerate() {
+            let e = exponent(v).ok_or(BoardError::Cell { cell: i, value: v })?;
+            b.set(i as u8, e);
+        }
+        Ok(b)
+    }
+
     pub fn game_ended(&self) -> bool {
-        (0..4)
-            .map(|i| {
-                let mut b: Board = *self;
-                b.make_move(Move::from_int(i))
-            })
-            .all(|x| x.is_none())
+        self.legal_moves().is_empty()
     }
 
     pub fn sum_tile(&self) -> u32 {
@@ -126,42 +257,24 @@ impl Board {
 
     pub fn move_left(&mut self) -> Option<u32> {
         let mut score = 0;
-        let mut did_move = false;
+        let mut delta = 0;
         for row in 0..4 {
-            let oldrow = self.get_row(row);
-            let (score_delta, newrow) = CACHE.get_left(oldrow);
-            if newrow != oldrow {
-                did_move = true;
-            }
-            self.set_row(row, newrow);
-            score += score_delta;
-        }
-
-        if did_move {
-            Some(score as u32)
-        } else {
-            None
+            let elem = CACHE.get(self.get_row(row));
+            delta |= (elem.left as u64) << (row << 4);
+            score += elem.score;
         }
+        self.apply(delta, score)
     }
 
     pub fn move_right(&mut self) -> Option<u32> {
         let mut score = 0;
-        let mut did_move = false;
+        let mut delta = 0;
         for row in 0..4 {
-            let oldrow = self.get_row(row);
-            let (score_delta, newrow) = CACHE.get_right(oldrow);
-            if newrow != oldrow {
-                did_move = true;
-            }
-            self.set_row(row, newrow);
-            score += score_delta;
-        }
-
-        if did_move {
-            Some(score as u32)
-        } else {
-            None
+            let elem = CACHE.get(self.get_row(row));
+            delta |= (elem.right as u64) << (row << 4);
+            score += elem.score;
         }
+        self.apply(delta, score)
     }
 
     pub fn _get_col(&self, i: u8) -> Row {
@@ -194,18 +307,76 @@ impl Board {
         self.raw = final_raw;
     }
 
+    /// Columns are read as the rows of the transposed board and looked up in
+    /// the column tables, whose entries are already laid out as columns.
     pub fn move_up(&mut self) -> Option<u32> {
-        self.counterclockwise();
-        let res = self.move_left();
-        self.clockwise();
-        res
+        let t = self.transposed();
+        let mut score = 0;
+        let mut delta = 0;
+        for col in 0..4 {
+            let row = t.get_row(col);
+            delta |= CACHE.up[row.raw as usize] << (col << 2);
+            score += CACHE.get(row).score;
+        }
+        self.apply(delta, score)
     }
 
     pub fn move_down(&mut self) -> Option<u32> {
-        self.clockwise();
-        let res = self.move_left();
-        self.counterclockwise();
-        res
+        let t = self.transposed();
+        let mut score = 0;
+        let mut delta = 0;
+        for col in 0..4 {
+            let row = t.get_row(col);
+            delta |= CACHE.down[row.raw as usize] << (col << 2);
+            score += CACHE.get(row).score;
+        }
+        self.apply(delta, score)
+    }
+
+    /// Flip the nibbles set in `delta`, the XOR of the board before and after
+    /// a move; an empty delta means the move was illegal.
+    fn apply(&mut self, delta: u64, score: u32) -> Option<u32> {
+        if delta == 0 {
+            return None;
+        }
+        self.raw ^= delta;
+        Some(score)
+    }
+
+    /// Each legal move with its afterstate and merge score; see
+    /// `GameBoard::afterstates`.
+    pub fn afterstates(&self) -> impl Iterator<Item = (Move, Board, u32)> + '_ {
+        GameBoard::afterstates(self)
+    }
+
+    /// The boards a turn's spawns can lead to, with their probabilities; see
+    /// `GameBoard::spawn_outcomes`.
+    pub fn spawn_outcomes(&self, rules: &SpawnRules) -> vec::IntoIter<(Board, f32)> {
+        GameBoard::spawn_outcomes(self, rules)
+    }
+
+    /// The moves that change the board.
+    pub fn legal_moves(&self) -> MoveSet {
+        let t = self.transposed();
+        let mut moves = MoveSet::empty();
+        for i in 0..4 {
+            let row = CACHE.get(self.get_row(i));
+            if row.left != 0 {
+                moves.insert(Move::Left);
+            }
+            if row.right != 0 {
+                moves.insert(Move::Right);
+            }
+            // a column slides up exactly when its transposed row slides left
+            let col = CACHE.get(t.get_row(i));
+            if col.left != 0 {
+                moves.insert(Move::Up);
+            }
+            if col.right != 0 {
+                moves.insert(Move::Down);
+            }
+        }
+        moves
     }
 
     pub const fn get(&self, i: u8, j: u8) -> u8 {
@@ -230,7 +401,7 @@ impl Board {
         self.raw = (self.raw & !((0xffff as u64) << (i << 4))) | ((row as u64) << (i << 4));
     }
 
-    pub fn transpose(&mut self) {
+    pub const fn transpose(&mut self) {
         let step1 = (self.raw & 0xf0f00f0ff0f00f0f)
             | ((self.raw & 0x0000f0f00000f0f0) << 12)
             | ((self.raw & 0x0f0f00000f0f0000) >> 12);
@@ -240,6 +411,12 @@ impl Board {
         self.raw = step2;
     }
 
+    pub const fn transposed(&self) -> Board {
+        let mut b = *self;
+        b.transpose();
+        b
+    }
+
     pub fn clockwise(&mut self) {
         self.transpose();
         self.flip_horizontal();
@@ -276,6 +453,11 @@ impl Board {
     }
 
     pub fn add_random_tile(&mut self, rng: &mut Rng) {
+        self.add_random_tile_with(&SpawnRules::CLASSIC, rng);
+    }
+
+    /// Add one tile drawn from `rules` to a random empty cell.
+    pub fn add_random_tile_with(&mut self, rules: &SpawnRules, rng: &mut Rng) {
         // let empty_spaces: [u8; 16] = (0..16 as u8).filter(|&i| self.at(i) == 0).collect();
         let mut len = 0;
         let mut empty_spaces: [u8; 16] = [0; 16];
@@ -286,10 +468,14 @@ impl Board {
             }
         }
         if len > 0 {
-            self.set(
-                empty_spaces[rng.usize(0..len)],
-                if rng.u8(0..10) != 0 { 1 } else { 2 },
-            );
+            self.set(empty_spaces[rng.usize(0..len)], rules.sample(rng));
+        }
+    }
+
+    /// Add the tiles that follow a move under `rules`.
+    pub fn spawn(&mut self, rules: &SpawnRules, rng: &mut Rng) {
+        for _ in 0..rules.per_move {
+            self.add_random_tile_with(rules, rng);
         }
     }
 
@@ -313,123 +499,53 @@ impl Board {
     }
 }
 
+/// What sliding a row does, as XOR masks over the row before the move.
 #[derive(Clone, Copy)]
 struct MoveCacheElem {
-    after: Row,
-    score: u16,
+    left: u16,
+    right: u16,
+    /// Merge score, the same either way: every run of equal tiles merges
+    /// into the same number of pairs whichever end it starts from.
+    score: u32,
 }
 
 struct MoveCache {
-    left_cache: [MoveCacheElem; 1 << 16],
+    rows: [MoveCacheElem; 1 << 16],
+    /// XOR masks for sliding a column up and down, indexed by the column read
+    /// as a row and spread out to one nibble every 16 bits.
+    up: [u64; 1 << 16],
+    down: [u64; 1 << 16],
 }
 
 impl MoveCache {
-    fn new() -> Self {
-        let default = MoveCacheElem {
-            after: Row::new(),
-            score: 0,
+    const fn new() -> Self {
+        // written by build.rs from src/row.rs
+        const BYTES: &[u8; 8 << 16] = include_bytes!(concat!(env!("OUT_DIR"), "/move_rows.bin"));
+        let mut cache = Self {
+            rows: [MoveCacheElem {
+                left: 0,
+                right: 0,
+                score: 0,
+            }; 1 << 16],
+            up: [0; 1 << 16],
+            down: [0; 1 << 16],
         };
-        let mut left_cache = [default; 1 << 16];
-        for i in 0x0000..=0xffff as u16 {
-            let row = Row::from_raw(i);
-            // Assume that we're not getting > 65k points in one shift
-            // Won't happen given that I've rarely gotten a single 32k tile
-            let (score, after) = row.shift_left();
-            let score = score as u16;
-            left_cache[i as usize] = MoveCacheElem { after, score };
+        let mut i = 0;
+        while i < 1 << 16 {
+            let b = 8 * i;
+            let left = u16::from_le_bytes([BYTES[b], BYTES[b + 1]]);
+            let right = u16::from_le_bytes([BYTES[b + 2], BYTES[b + 3]]);
+            let score =
+                u32::from_le_bytes([BYTES[b + 4], BYTES[b + 5], BYTES[b + 6], BYTES[b + 7]]);
+            cache.rows[i] = MoveCacheElem { left, right, score };
+            cache.up[i] = Row::from_raw(left).to_col();
+            cache.down[i] = Row::from_raw(right).to_col();
+            i += 1;
         }
-        Self { left_cache }
+        cache
     }
 
-    fn get_left(&self, i: Row) -> (u16, Row) {
-        let res = self.left_cache[i.raw as usize];
-        return (res.score, res.after);
-    }
-
-    fn get_right(&self, i: Row) -> (u16, Row) {
-        let res = self.left_cache[i.reverse().raw as usize];
-        return (res.score, res.after.reverse());
-    }
-}
-
-#[derive(Clone, Copy, PartialEq)]
-pub struct Row {
-    pub raw: u16,
-}
-
-impl fmt::Display for Row {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut board_str = String::new();
-        for x in 0..4 {
-            let value = self.get(x);
-            board_str.push_str(&format!("{} ", value));
-        }
-        board_str.pop(); // Remove the last space
-        board_str.push('\n'); // Remove the last space
-        write!(f, "{}", board_str)
-    }
-}
-
-impl Row {
-    pub const fn new() -> Self {
-        Self { raw: 0 }
-    }
-    pub const fn from_raw(r: u16) -> Self {
-        Self { raw: r }
-    }
-    pub fn get(&self, i: u8) -> u8 {
-        return ((self.raw >> (i * 4)) & 0x0f) as u8;
-    }
-    pub fn set(&mut self, i: u8, r: u8) {
-        let pos = i * 4;
-        let masked_raw = self.raw & !(0xf << pos);
-        let mod_raw = masked_raw | ((r as u16) << pos);
-        self.raw = mod_raw;
-    }
-    pub fn shift_left(&self) -> (u32, Self) {
-        let mut score: u32 = 0;
-        let mut top = 0;
-        let mut tmp = 0;
-        let mut row: Self = *self;
-
-        for i in 0..4 {
-            let mut tile = self.get(i);
-            if tile == 0 {
-                continue;
-            }
-            row.set(i, 0);
-            if tmp != 0 {
-                if tile == tmp {
-                    tile = tile + 1;
-                    row.set(top, tile);
-                    top += 1;
-                    score += 1 << tile;
-                    tmp = 0;
-                } else {
-                    row.set(top, tmp);
-                    top += 1;
-                    tmp = tile;
-                }
-            } else {
-                tmp = tile;
-            }
-        }
-        if tmp != 0 {
-            row.set(top, tmp);
-        }
-        return (score, row);
-    }
-    pub fn reverse(&self) -> Self {
-        let raw = self.raw;
-        let p1 = (raw & 0x000f) << 3 * 4;
-        let p2 = (raw & 0x00f0) << 1 * 4;
-        let p3 = (raw & 0x0f00) >> 1 * 4;
-        let p4 = (raw & 0xf000) >> 3 * 4;
-        return Self::from_raw(p1 | p2 | p3 | p4);
-    }
-    pub fn shift_right(&self) -> (u32, Self) {
-        let rev = self.reverse();
-        let (score, shifted) = rev.shift_left();
-        return (score, shifted.reverse());
+    fn get(&self, row: Row) -> MoveCacheElem {
+        self.rows[row.raw as usize]
     }
 }
@@ -0,0 +1,210 @@
+//! Players described by data, e.g. `{"time_ms": 50, "heuristic":
+//! {"empty_weight": 300}}` for an expectimax player, so front ends can map
+//! their settings to engine parameters, and their choices as data in turn:
+//! `{"move": 3, "scores": [null, 812.5, null, 1040.2]}`.
+use crate::expectimax::best_of;
+use crate::{
+    Board, ConfigHeuristic, DepthPolicy, Evaluator, ExpectimaxPlayer, HeuristicParams,
+    MonteCarloMetric, MonteCarloPlayer, Move, NTuple, Player, SpawnRules,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+/// Settings for a `MonteCarloPlayer`. Missing fields keep their defaults.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MonteCarloConfig {
+    /// Random games played out after each move.
+    pub rollouts: u32,
+    pub metric: MonteCarloMetric,
+    pub rules: SpawnRules,
+}
+
+impl Default for MonteCarloConfig {
+    fn default() -> Self {
+        Self {
+            rollouts: 200,
+            metric: MonteCarloMetric::Sum,
+            rules: SpawnRules::CLASSIC,
+        }
+    }
+}
+
+impl MonteCarloConfig {
+    pub fn player(&self) -> MonteCarloPlayer {
+        MonteCarloPlayer::new(self.rollouts, self.metric.clone()).with_rules(self.rules)
+    }
+}
+
+/// Settings for an `ExpectimaxPlayer`. Missing fields keep their defaults.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExpectimaxConfig {
+    /// Plies to search below each move. Without this or `time_ms` the depth
+    /// follows the board, as for `ExpectimaxPlayer::default()`.
+    pub depth: Option<u32>,
+    /// Deepen one ply at a time while the next is expected to finish within
+    /// this many milliseconds. Takes precedence over `depth`.
+    pub time_ms: Option<f64>,
+    /// The deepest a time budget may search.
+    pub max_depth: u32,
+    #[serde(with = "HeuristicParamsDef")]
+    pub heuristic: HeuristicParams,
+    pub rules: SpawnRules,
+}
+
+impl Default for ExpectimaxConfig {
+    fn default() -> Self {
+        Self {
+            depth: None,
+            time_ms: None,
+            max_depth: 10,
+            heuristic: HeuristicParams::DEFAULT,
+            rules: SpawnRules::CLASSIC,
+        }
+    }
+}
+
+impl ExpectimaxConfig {
+    pub fn player(&self) -> ExpectimaxPlayer<ConfigHeuristic> {
+        let depth = match (self.time_ms, self.depth) {
+            (Some(ms), _) => DepthPolicy::Time {
+                budget: Duration::from_secs_f64(ms.max(0.0) / 1000.0),
+                max: self.max_depth,
+            },
+            (None, Some(depth)) => DepthPolicy::Fixed(depth),
+            (None, None) => DepthPolicy::default(),
+        };
+        ExpectimaxPlayer::new(ConfigHeuristic::new(self.heuristic))
+            .with_depth_policy(depth)
+            .with_rules(self.rules)
+    }
+}
+
+/// A player as front ends name it, with its config. An n-tuple player has
+/// no settings of its own; its network is loaded separately.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlayerConfig {
+    Expectimax(ExpectimaxConfig),
+    MonteCarlo(MonteCarloConfig),
+    NTuple,
+}
+
+impl PlayerConfig {
+    /// The player names `from_options` accepts.
+    pub const NAMES: [&'static str; 3] = ["expectimax", "monte_carlo", "ntuple"];
+
+    /// The player called `player`, with the config fields in `options`.
+    pub fn from_options(player: &str, options: &Map<String, Value>) -> Result<Self, String> {
+        let config = Value::Object(options.clone());
+        let err = |e: serde_json::Error| format!("{player}: {e}");
+        match player {
+            "expectimax" => Ok(Self::Expectimax(
+                serde_json::from_value(config).map_err(err)?,
+            )),
+            "monte_carlo" => Ok(Self::MonteCarlo(
+                serde_json::from_value(config).map_err(err)?,
+            )),
+            "ntuple" => match options.keys().next() {
+                Some(key) => Err(format!("{player}: unknown option {key:?}")),
+                None => Ok(Self::NTuple),
+            },
+            _ => Err(format!("no player named {player:?}")),
+        }
+    }
+
+    /// This player's choice at `b`, with `net` as the n-tuple network. None
+    /// if the player needs a network and `net` is None.
+    pub fn choice(&self, b: &Board, net: Option<&NTuple>) -> Option<MoveChoice> {
+        match self {
+            Self::Expectimax(config) => Some(config.player().choice(b)),
+            Self::MonteCarlo(config) => Some(config.player().choice(b)),
+            Self::NTuple => net.map(|net| net.choice(b)),
+        }
+    }
+}
+
+/// `HeuristicParams` as config, by the same field names. Weights left out
+/// keep their tuned values.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "HeuristicParams", default, deny_unknown_fields)]
+struct HeuristicParamsDef {
+    lost_penalty: f32,
+    monotonicity_power: f32,
+    monotonicity_weight: f32,
+    sum_power: f32,
+    sum_weight: f32,
+    merges_weight: f32,
+    empty_weight: f32,
+}
+
+impl Default for HeuristicParamsDef {
+    fn default() -> Self {
+        let p = HeuristicParams::DEFAULT;
+        Self {
+            lost_penalty: p.lost_penalty,
+            monotonicity_power: p.monotonicity_power,
+            monotonicity_weight: p.monotonicity_weight,
+            sum_power: p.sum_power,
+            sum_weight: p.sum_weight,
+            merges_weight: p.merges_weight,
+            empty_weight: p.empty_weight,
+        }
+    }
+}
+
+/// A player's move with the score it gave every move. `mv` is numbered as by
+/// `Move::to_int`, -1 once the game is over, and `scores` is indexed the same
+/// way, with `None` for illegal moves.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MoveChoice {
+    #[serde(rename = "move")]
+    pub mv: i32,
+    pub scores: [Option<f32>; 4],
+}
+
+impl MoveChoice {
+    fn new(best: Option<Move>, scores: &[(Move, f32)]) -> Self {
+        let mut by_int = [None; 4];
+        for &(m, score) in scores {
+            by_int[m.to_int() as usize] = Some(score);
+        }
+        Self {
+            mv: best.map_or(-1, |m| m.to_int()),
+            scores: by_int,
+        }
+    }
+}
+
+impl MonteCarloPlayer {
+    /// The move `next_move` makes, with the average rollout result of every
+    /// move.
+    pub fn choice(&self, b: &Board) -> MoveChoice {
+        let totals = self.rollout_totals(b);
+        let best = totals.iter().max_by_key(|&&(_, s)| s).map(|&(m, _)| m);
+        let scores: Vec<_> = totals.iter().map(|&(m, t)| (m, self.average(t))).collect();
+        MoveChoice::new(best, &scores)
+    }
+}
+
+impl<E: Evaluator> ExpectimaxPlayer<E> {
+    /// The move `next_move` makes, with the search value of every move.
+    pub fn choice(&self, b: &Board) -> MoveChoice {
+        let scores = self.move_scores(b);
+        let best = best_of(&scores).map(|(_, m)| m);
+        MoveChoice::new(best, &scores)
+    }
+}
+
+impl NTuple {
+    /// The move `next_move` makes, with the merge score plus afterstate
+    /// estimate of every move.
+    pub fn choice(&self, b: &Board) -> MoveChoice {
+        let scores: Vec<_> = b
+            .afterstates()
+            .map(|(m, after, reward)| (m, reward as f32 + self.estimate(&after)))
+            .collect();
+        MoveChoice::new(self.next_move(b), &scores)
+    }
+}
@@ -0,0 +1,232 @@
+//! A line protocol for driving the solvers as a subprocess, in the spirit of
+//! chess's UCI. The front end writes commands, one per line:
+//!
+//! - `uei`: the engine names itself and its players, then says `ueiok`;
+//! - `isready`: answered with `readyok`;
+//! - `setoption player expectimax depth 4`: pick a player and set fields of
+//!   its config by name, with dots for nested fields
+//!   (`heuristic.empty_weight 300`). Without `player` the fields are set on
+//!   the current player. `ntuple` takes a weight file: `weights <path>`;
+//! - `position <board>`: the board in any notation `Board` parses, or the
+//!   web page's saved game as JSON;
+//! - `go`, optionally with limits for this search only: `movetime <ms>`,
+//!   `depth <plies>`, `rollouts <games>`. A player ignores the limits it
+//!   has no setting for: `monte_carlo` plays its rollouts whatever
+//!   `movetime` says;
+//! - `quit`.
+//!
+//! `go` answers with an `info move <move> score <value>` line per legal
+//! move, `info time <ms>`, and `bestmove <move>`, or `bestmove none` once
+//! the game is over. Anything that goes wrong is reported as
+//! `info string error: ...`, and the engine carries on.
+use crate::{
+    Board, ExpectimaxConfig, Move, NTuple, ParseError, PlayerConfig, WebState, WeightFormat,
+};
+use serde_json::{Map, Value};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::time::Instant;
+
+/// The players `setoption player` accepts.
+pub const ENGINE_PLAYERS: [&str; 3] = PlayerConfig::NAMES;
+
+/// How `go` limits map onto the config fields of the player that has them.
+const GO_LIMITS: [(&str, &str, &str); 3] = [
+    ("movetime", "expectimax", "time_ms"),
+    ("depth", "expectimax", "depth"),
+    ("rollouts", "monte_carlo", "rollouts"),
+];
+
+/// What `go` found.
+struct Search {
+    best: Option<Move>,
+    /// The score of each legal move, in the order of `Move::all`.
+    scores: Vec<(Move, f32)>,
+    ms: f64,
+}
+
+/// The state of one protocol session: the player, its options, the
+/// network and the position.
+pub struct Engine {
+    player: String,
+    options: Map<String, Value>,
+    config: PlayerConfig,
+    /// The last weight file loaded, kept across changes of player.
+    net: Option<NTuple>,
+    board: Option<Board>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self {
+            player: "expectimax".to_string(),
+            options: Map::new(),
+            config: PlayerConfig::Expectimax(ExpectimaxConfig::default()),
+            net: None,
+            board: None,
+        }
+    }
+}
+
+impl Engine {
+    /// Answer commands from `input` until it ends or says `quit`.
+    pub fn run(&mut self, input: impl BufRead, out: &mut impl Write) -> io::Result<()> {
+        for line in input.lines() {
+            if !self.command(&line?, out)? {
+                break;
+            }
+            out.flush()?;
+        }
+        out.flush()
+    }
+
+    /// Answer one command, returning false for `quit`.
+    pub fn command(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let line = line.trim();
+        let (cmd, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let result = match cmd {
+            "" => Ok(()),
+            "quit" => return Ok(false),
+            "uei" => {
+                writeln!(out, "id name solve2048 {}", env!("CARGO_PKG_VERSION"))?;
+                writeln!(out, "option player {}", ENGINE_PLAYERS.join(" "))?;
+                writeln!(out, "ueiok")?;
+                Ok(())
+            }
+            "isready" => {
+                writeln!(out, "readyok")?;
+                Ok(())
+            }
+            "setoption" => self.set_options(args),
+            "position" => self.set_position(args.trim()),
+            "go" => match self.search(args) {
+                Ok(search) => {
+                    for (m, score) in search.scores {
+                        writeln!(out, "info move {m} score {score}")?;
+                    }
+                    writeln!(out, "info time {:.0}", search.ms)?;
+                    match search.best {
+                        Some(m) => writeln!(out, "bestmove {m}")?,
+                        None => writeln!(out, "bestmove none")?,
+                    }
+                    Ok(())
+                }
+                Err(msg) => Err(msg),
+            },
+            _ => Err(format!("unknown command {cmd:?}")),
+        };
+        if let Err(msg) = result {
+            writeln!(out, "info string error: {msg}")?;
+        }
+        Ok(true)
+    }
+
+    fn set_options(&mut self, args: &str) -> Result<(), String> {
+        let mut player = self.player.clone();
+        let mut options = self.options.clone();
+        let mut net = None;
+        for (key, value) in pairs(args)? {
+            match key {
+                "player" => {
+                    player = value.to_string();
+                    options.clear();
+                }
+                "weights" => net = Some(load_weights(value)?),
+                _ => set_field(&mut options, key, value),
+            }
+        }
+        self.config = PlayerConfig::from_options(&player, &options)?;
+        self.player = player;
+        self.options = options;
+        if net.is_some() {
+            self.net = net;
+        }
+        Ok(())
+    }
+
+    fn set_position(&mut self, board: &str) -> Result<(), String> {
+        let b = if board.starts_with('{') {
+            let state: WebState = serde_json::from_str(board).map_err(|e| e.to_string())?;
+            state.board().map_err(|e| e.to_string())?
+        } else {
+            board.parse().map_err(|e: ParseError| e.to_string())?
+        };
+        self.board = Some(b);
+        Ok(())
+    }
+
+    /// Search the position under the limits in `args`.
+    fn search(&self, args: &str) -> Result<Search, String> {
+        let b = self.board.ok_or("no position")?;
+        let limits = pairs(args)?;
+        let config = if limits.is_empty() {
+            None
+        } else {
+            let mut options = self.options.clone();
+            for (limit, value) in limits {
+                let &(_, player, field) = GO_LIMITS
+                    .iter()
+                    .find(|(l, _, _)| *l == limit)
+                    .ok_or_else(|| format!("unknown limit {limit:?}"))?;
+                if player != self.player {
+                    continue;
+                }
+                if field == "depth" {
+                    // a time budget would take precedence
+                    options.remove("time_ms");
+                }
+                set_field(&mut options, field, value);
+            }
+            Some(PlayerConfig::from_options(&self.player, &options)?)
+        };
+        let start = Instant::now();
+        let choice = config
+            .as_ref()
+            .unwrap_or(&self.config)
+            .choice(&b, self.net.as_ref())
+            .ok_or("ntuple needs weights")?;
+        let ms = start.elapsed().as_secs_f64() * 1000.0;
+        let best = (choice.mv >= 0).then(|| Move::from_int(choice.mv as u32));
+        let scores = Move::all()
+            .into_iter()
+            .filter_map(|m| Some((m, choice.scores[m.to_int() as usize]?)))
+            .collect();
+        Ok(Search { best, scores, ms })
+    }
+}
+
+/// Arguments as `key value` pairs.
+fn pairs(args: &str) -> Result<Vec<(&str, &str)>, String> {
+    let words: Vec<&str> = args.split_whitespace().collect();
+    if !words.len().is_multiple_of(2) {
+        return Err(format!("{:?} has no value", words[words.len() - 1]));
+    }
+    Ok(words.chunks(2).map(|kv| (kv[0], kv[1])).collect())
+}
+
+/// Set the field at the dotted path `key`. Values are read as JSON where
+/// they can be, so `4` is a number and `max_tile` a string.
+fn set_field(options: &mut Map<String, Value>, key: &str, value: &str) {
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+    let mut fields = key.split('.').peekable();
+    let mut map = options;
+    while let Some(field) = fields.next() {
+        if fields.peek().is_none() {
+            map.insert(field.to_string(), value);
+            return;
+        }
+        let entry = map
+            .entry(field)
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !entry.is_object() {
+            *entry = Value::Object(Map::new());
+        }
+        map = entry.as_object_mut().unwrap();
+    }
+}
+
+fn load_weights(path: &str) -> Result<NTuple, String> {
+    let file = File::open(path).map_err(|e| format!("{path}: {e}"))?;
+    NTuple::import(&mut BufReader::new(file), &WeightFormat::native())
+        .map_err(|e| format!("{path}: {e}"))
+}
@@ -1,238 +1,847 @@
-use crate::{Board, Move, Player};
-use lazy_static::lazy_static;
-use rayon::prelude::*;
+use crate::heuristic_row::{compute_row_score, HeuristicParams};
+use crate::par::*;
+use crate::row::Row;
+use crate::{Board, Move, Player, SpawnRules};
+use fastrand::Rng;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+use std::time::Instant;
 
-lazy_static! {
-    static ref CACHE: HeuristicScoreCache = HeuristicScoreCache::new();
-    // static ref SYNC: SyncCache<Board, f32> = SyncCache::new(1_000_000);
+/// Tabulated at build time, so there's nothing to initialise at startup.
+static CACHE: HeuristicScoreCache = HeuristicScoreCache::new();
+
+/// Scores the boards at the frontier of a search.
+pub trait Evaluator: Sync {
+    /// Value of the afterstate `b`: the board after a move, before a tile spawns.
+    fn evaluate(&self, b: &Board) -> f32;
+
+    /// Whether the search should add the score of each move to the leaf value.
+    /// Learned value functions like `NTuple` only predict the score still to
+    /// come, so paths must be credited with what they already collected.
+    fn counts_score(&self) -> bool {
+        false
+    }
+
+    /// Lower and upper bounds on `evaluate`, which must also contain 0 (the
+    /// value of a lost position). Star1/Star2 pruning needs them.
+    fn bounds(&self) -> Option<(f32, f32)> {
+        None
+    }
+}
+
+impl<E: Evaluator + ?Sized> Evaluator for &E {
+    fn evaluate(&self, b: &Board) -> f32 {
+        (**self).evaluate(b)
+    }
+
+    fn counts_score(&self) -> bool {
+        (**self).counts_score()
+    }
+
+    fn bounds(&self) -> Option<(f32, f32)> {
+        (**self).bounds()
+    }
 }
 
+/// Hand-tuned monotonicity/merge/empty-cell heuristic.
 #[derive(Clone, Copy, Default)]
-pub struct ExpectimaxPlayer {
-    depth_limit: u32,
-    maxdepth: u32,
-    curdepth: u32,
-    cache_hits: u32,
-    moves_simulated: u32,
+pub struct Heuristic;
+
+impl Evaluator for Heuristic {
+    fn evaluate(&self, b: &Board) -> f32 {
+        CACHE.get_score(b)
+    }
+
+    fn bounds(&self) -> Option<(f32, f32)> {
+        Some(CACHE.bounds())
+    }
 }
 
-impl Player for ExpectimaxPlayer {
-    fn next_move(&self, b: &Board) -> Option<Move> {
-        let mut best_move = Move::Left;
-        let mut best_score = 0.0;
-        let results = Move::all()
-            // .iter()
-            .par_iter()
-            .map(|&m| {
-                let score = self.move_score(b, m);
-                (score, m)
+/// `Heuristic` with other weights. The row table is computed when it's built
+/// rather than at compile time, and clones share it.
+#[derive(Clone)]
+pub struct TunedHeuristic {
+    params: HeuristicParams,
+    rows: Arc<[f32]>,
+    bounds: (f32, f32),
+}
+
+impl TunedHeuristic {
+    pub fn new(params: HeuristicParams) -> Self {
+        let rows: Arc<[f32]> = (0..=u16::MAX)
+            .map(|i| {
+                let row = Row::from_raw(i);
+                compute_row_score(&[0, 1, 2, 3].map(|j| row.get(j)), &params)
             })
-            .collect::<Vec<_>>();
-        for (score, mv) in results {
-            if score > best_score {
-                best_score = score;
-                best_move = mv;
+            .collect();
+        let row_min = rows.iter().copied().fold(f32::INFINITY, f32::min);
+        let row_max = rows.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        Self {
+            params,
+            rows,
+            bounds: board_bounds(row_min, row_max),
+        }
+    }
+
+    pub fn params(&self) -> &HeuristicParams {
+        &self.params
+    }
+}
+
+impl Evaluator for TunedHeuristic {
+    fn evaluate(&self, b: &Board) -> f32 {
+        rows_score(&self.rows, b)
+    }
+
+    fn bounds(&self) -> Option<(f32, f32)> {
+        Some(self.bounds)
+    }
+}
+
+/// How many tuned tables `ConfigHeuristic::new` keeps for reuse.
+const TUNED_TABLES_KEPT: usize = 8;
+
+/// The heuristic for a set of weights: `Heuristic` for the default ones, so
+/// nothing needs computing, and otherwise a `TunedHeuristic` sharing its table
+/// with recently built ones of the same weights.
+#[derive(Clone)]
+pub enum ConfigHeuristic {
+    Default(Heuristic),
+    Tuned(TunedHeuristic),
+}
+
+impl ConfigHeuristic {
+    pub fn new(params: HeuristicParams) -> Self {
+        static TABLES: Mutex<Vec<TunedHeuristic>> = Mutex::new(Vec::new());
+        if params == HeuristicParams::DEFAULT {
+            return Self::Default(Heuristic);
+        }
+        // held while building, so concurrent requests build a table once
+        let mut tables = TABLES.lock().unwrap();
+        let tuned = match tables.iter().position(|t| t.params == params) {
+            Some(i) => tables.remove(i),
+            None => TunedHeuristic::new(params),
+        };
+        if tables.len() == TUNED_TABLES_KEPT {
+            tables.remove(0);
+        }
+        tables.push(tuned.clone());
+        Self::Tuned(tuned)
+    }
+}
+
+impl Evaluator for ConfigHeuristic {
+    fn evaluate(&self, b: &Board) -> f32 {
+        match self {
+            Self::Default(h) => h.evaluate(b),
+            Self::Tuned(h) => h.evaluate(b),
+        }
+    }
+
+    fn bounds(&self) -> Option<(f32, f32)> {
+        match self {
+            Self::Default(h) => h.bounds(),
+            Self::Tuned(h) => h.bounds(),
+        }
+    }
+}
+
+/// Alpha-beta style pruning of chance nodes (Ballard's *-minimax family).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Star {
+    Off,
+    /// Cut a chance node once its remaining outcomes can't bring it back
+    /// inside the search window.
+    Star1,
+    /// Star1, after first probing one move of every outcome for a lower bound.
+    Star2,
+}
+
+/// Ways to cut the cost of chance nodes.
+#[derive(Clone, Copy, Debug)]
+pub struct ChancePruning {
+    /// Evaluate instead of expanding a node whose probability of being
+    /// reached is below this.
+    pub prob_cutoff: f32,
+    /// Only takes effect with an evaluator that has bounds and doesn't count
+    /// score, since the window arithmetic relies on both.
+    pub star: Star,
+    /// Spawn into at most this many of the empty cells, chosen at random,
+    /// when a board has more. Boards early in the game have many empty cells
+    /// that mostly lead to similar positions.
+    pub sample_cells: Option<u8>,
+}
+
+impl Default for ChancePruning {
+    fn default() -> Self {
+        Self {
+            prob_cutoff: 0.0001,
+            star: Star::Off,
+            sample_cells: None,
+        }
+    }
+}
+
+/// How many spawn/move plies to search below each root move. 0 evaluates the
+/// root afterstates directly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DepthPolicy {
+    Fixed(u32),
+    /// The number of distinct cell values (empty included) less two, so
+    /// positions with more to merge get searched further.
+    DistinctTiles {
+        min: u32,
+        max: u32,
+    },
+    /// Deeper as the board fills up: `max` with no empty cells, down to `min`
+    /// with fifteen.
+    EmptyCells {
+        min: u32,
+        max: u32,
+    },
+    /// Iterative deepening from 1 ply until the next iteration is predicted
+    /// to overrun `budget`, or `max` is reached.
+    Time {
+        budget: Duration,
+        max: u32,
+    },
+}
+
+impl Default for DepthPolicy {
+    fn default() -> Self {
+        Self::DistinctTiles { min: 3, max: 10 }
+    }
+}
+
+impl DepthPolicy {
+    /// The depth to search `b` at. For `Time` this is the deepest iteration.
+    pub fn depth(&self, b: &Board) -> u32 {
+        match *self {
+            Self::Fixed(depth) => depth,
+            Self::DistinctTiles { min, max } => {
+                let distinct = b.distinct_tiles() as u32;
+                distinct.saturating_sub(2).clamp(min, max.max(min))
             }
+            Self::EmptyCells { min, max } => {
+                let max = max.max(min);
+                let empty = (b.num_empty() as u32).min(15);
+                max - (max - min) * empty / 15
+            }
+            Self::Time { max, .. } => max,
         }
-        if best_score == 0.0 {
-            None
-        } else {
-            Some(best_move)
+    }
+}
+
+/// What the search assumes about where tiles spawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChanceModel {
+    /// At random according to the spawn rules.
+    Expected,
+    /// Wherever hurts the player most, with any tile the rules allow.
+    Worst,
+}
+
+#[derive(Clone, Copy)]
+pub struct ExpectimaxPlayer<E = Heuristic> {
+    eval: E,
+    depth: DepthPolicy,
+    pruning: ChancePruning,
+    parallel_depth: u32,
+    rules: SpawnRules,
+    chance: ChanceModel,
+}
+
+impl Default for ExpectimaxPlayer {
+    fn default() -> Self {
+        Self::new(Heuristic)
+    }
+}
+
+impl<E: Evaluator> Player for ExpectimaxPlayer<E> {
+    fn next_move(&self, b: &Board) -> Option<Move> {
+        self.next_move_with_eval(b).map(|(mv, _)| mv)
+    }
+
+    /// The value is the search value of the chosen move.
+    fn next_move_with_eval(&self, b: &Board) -> Option<(Move, Option<f32>)> {
+        best_of(&self.move_scores(b)).map(|(v, mv)| (mv, Some(v)))
+    }
+}
+
+/// The first of the highest scoring moves.
+pub(crate) fn best_of(scores: &[(Move, f32)]) -> Option<(f32, Move)> {
+    let mut best = None;
+    for &(mv, score) in scores {
+        match best {
+            Some((best_score, _)) if best_score >= score => (),
+            _ => best = Some((score, mv)),
         }
     }
+    best
+}
+
+/// Measures search time. `Instant` panics on wasm32-unknown-unknown, so
+/// the browser's clock stands in for the wasm bindings.
+struct Stopwatch {
+    #[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+    start: Instant,
+    #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+    start_ms: f64,
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+#[wasm_bindgen::prelude::wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = Date)]
+    fn now() -> f64;
+}
+
+impl Stopwatch {
+    fn start() -> Self {
+        Self {
+            #[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+            start: Instant::now(),
+            #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+            start_ms: now(),
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        #[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+        return self.start.elapsed();
+        #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+        return Duration::from_secs_f64((now() - self.start_ms).max(0.0) / 1000.0);
+    }
 }
 
-impl ExpectimaxPlayer {
-    /// Dont recurse if the probability of a new tile is below this threshold.
-    const CPROB_THRESH_BASE: f32 = 0.0001;
+impl<E: Evaluator> ExpectimaxPlayer<E> {
     /// Max depth of cached nodes to avoid excessive memory usage.
     const CACHE_DEPTH_LIMIT: u32 = 15;
 
-    /// Returns the expected score of the given move.
-    fn move_score(&self, b: &Board, m: Move) -> f32 {
-        let mut self_copy = Self::default();
-        self_copy.depth_limit = (b.distinct_tiles() - 2).max(3) as u32;
-        // self_copy.depth_limit = 3;
-        let mut map = HashMap::new();
-
-        let mut b_copy = b.clone();
-        if b_copy.make_move(m).is_some() {
-            // if the move is valid, explore
-            let res = self_copy.random_player_score(&b_copy, 1.0, &mut map) + 1e-6;
-            // println!(
-            //     "For move {:?}: moves simulated: {} cache size: {}, cache hits: {}",
-            //     m,
-            //     self_copy.moves_simulated,
-            //     map.len(),
-            //     self_copy.cache_hits
-            // );
-            res
+    /// Search with `eval` at the leaves, e.g. `ExpectimaxPlayer::new(&net)`
+    /// for a trained `NTuple`.
+    pub fn new(eval: E) -> Self {
+        Self {
+            eval,
+            depth: DepthPolicy::default(),
+            pruning: ChancePruning::default(),
+            parallel_depth: 1,
+            rules: SpawnRules::CLASSIC,
+            chance: ChanceModel::Expected,
+        }
+    }
+
+    /// The minimax counterpart: a search that takes the minimum rather than
+    /// the expectation at chance nodes, i.e. plays against the worst spawns.
+    /// Star pruning and the probability cutoff don't apply; plain alpha-beta
+    /// cuts do.
+    pub fn minimax(eval: E) -> Self {
+        Self::new(eval).with_chance_model(ChanceModel::Worst)
+    }
+
+    pub fn with_chance_model(mut self, chance: ChanceModel) -> Self {
+        self.chance = chance;
+        self
+    }
+
+    /// The tile placement (cell, exponent) after which the player's best
+    /// move is worth least, searching `depth` player moves from there.
+    /// `None` if `b` is full.
+    pub fn worst_spawn(&self, b: &Board, depth: u32) -> Option<(u8, u8)> {
+        let search = Search::new(self, depth);
+        let (alpha, _) = search.window();
+        let mut worst: Option<(f32, u8, u8)> = None;
+        for i in (0..16).filter(|&i| b.at(i) == 0) {
+            for (e, _) in self.rules.tiles() {
+                let mut child = *b;
+                child.set(i, e);
+                let beta = worst.map_or(f32::INFINITY, |(v, _, _)| v);
+                let v = search.best_move_player_score(&child, 0, 1.0, alpha, beta, None);
+                if worst.is_none_or(|(w, _, _)| v < w) {
+                    worst = Some((v, i, e));
+                }
+            }
+        }
+        worst.map(|(_, i, e)| (i, e))
+    }
+
+    /// Search a fixed number of spawn/move plies below the root move instead
+    /// of deriving the depth from the board. 0 evaluates the root afterstates.
+    pub fn with_depth(self, depth: u32) -> Self {
+        self.with_depth_policy(DepthPolicy::Fixed(depth))
+    }
+
+    pub fn with_depth_policy(mut self, depth: DepthPolicy) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// The search value of each legal move of `b`, searched as deep as the
+    /// depth policy says. With `DepthPolicy::Time` these come from the
+    /// deepest iteration that finished.
+    pub fn move_scores(&self, b: &Board) -> Vec<(Move, f32)> {
+        let DepthPolicy::Time { budget, max } = self.depth else {
+            return self.root_scores(b, self.depth.depth(b));
+        };
+
+        let start = Stopwatch::start();
+        let mut scores = Vec::new();
+        let mut last = Duration::ZERO;
+        for depth in 1..=max.max(1) {
+            let iter_start = Stopwatch::start();
+            scores = self.root_scores(b, depth);
+            let elapsed = iter_start.elapsed();
+            // assume the next ply costs at least twice this one
+            let growth = if last.is_zero() {
+                2.0
+            } else {
+                (elapsed.as_secs_f64() / last.as_secs_f64()).max(2.0)
+            };
+            last = elapsed;
+            if scores.is_empty() || start.elapsed() + last.mul_f64(growth) > budget {
+                break;
+            }
+        }
+        scores
+    }
+
+    /// Best root move and its value at a fixed depth.
+    #[cfg(test)]
+    pub(crate) fn search(&self, b: &Board, depth: u32) -> Option<(f32, Move)> {
+        best_of(&self.root_scores(b, depth))
+    }
+
+    /// The value of each root move at a fixed depth, in `Move::all` order.
+    fn root_scores(&self, b: &Board, depth: u32) -> Vec<(Move, f32)> {
+        let afterstates: Vec<_> = b.afterstates().collect();
+        afterstates
+            .par_iter()
+            .map(|&(m, after, reward)| {
+                let search = Search::new(self, depth);
+                (m, self.move_score(&search, &after, reward))
+            })
+            .collect()
+    }
+
+    /// Expect tiles to spawn according to `rules` rather than the classic game.
+    pub fn with_rules(mut self, rules: SpawnRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn rules(&self) -> &SpawnRules {
+        &self.rules
+    }
+
+    pub fn with_pruning(mut self, pruning: ChancePruning) -> Self {
+        self.pruning = pruning;
+        self
+    }
+
+    /// Expand the outcomes of chance nodes less than `depth` plies below the
+    /// root as separate rayon tasks (with the `rayon` feature). The four root
+    /// moves are always searched in parallel; 0 stops there. Star pruning is
+    /// skipped at these nodes, since their outcomes are no longer searched one
+    /// after another. The tasks under a root move share its transposition
+    /// table, so which of them caches a position first depends on
+    /// scheduling; with 0 every search is sequential and repeatable.
+    pub fn with_parallel_depth(mut self, depth: u32) -> Self {
+        self.parallel_depth = depth;
+        self
+    }
+
+    /// Returns the expected score of a root move leading to `after`.
+    fn move_score(&self, search: &Search<E>, after: &Board, reward: u32) -> f32 {
+        let (alpha, beta) = search.window();
+        let res = search.random_player_score(after, 0, 1.0, alpha, beta);
+        self.reward(reward) + res
+    }
+
+    /// The part of a move's score that counts towards the search value.
+    fn reward(&self, score: u32) -> f32 {
+        if self.eval.counts_score() {
+            score as f32
         } else {
             0.0
         }
     }
+}
+
+/// Transposition table shared by every task searching a root move, split
+/// into independently locked shards so threads rarely wait on each other.
+struct TranspositionTable {
+    shards: Vec<Mutex<HashMap<Board, (u32, f32)>>>,
+}
+
+impl TranspositionTable {
+    const SHARD_BITS: u32 = 6;
+
+    fn new() -> Self {
+        let shards = (0..1 << Self::SHARD_BITS)
+            .map(|_| Mutex::new(HashMap::new()))
+            .collect();
+        Self { shards }
+    }
+
+    fn shard(&self, b: &Board) -> &Mutex<HashMap<Board, (u32, f32)>> {
+        // Fibonacci hashing spreads boards that differ in a few nibbles
+        let i = b.raw.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> (64 - Self::SHARD_BITS);
+        &self.shards[i as usize]
+    }
+
+    fn get(&self, b: &Board) -> Option<(u32, f32)> {
+        self.shard(b).lock().unwrap().get(b).copied()
+    }
+
+    fn insert(&self, b: Board, depth: u32, score: f32) {
+        self.shard(&b).lock().unwrap().insert(b, (depth, score));
+    }
+}
+
+/// State of the search below one root move.
+struct Search<'a, E> {
+    player: &'a ExpectimaxPlayer<E>,
+    depth_limit: u32,
+    /// Evaluator bounds when Star pruning is in effect.
+    bounds: Option<(f32, f32)>,
+    map: TranspositionTable,
+}
+
+impl<'a, E: Evaluator> Search<'a, E> {
+    fn new(player: &'a ExpectimaxPlayer<E>, depth_limit: u32) -> Self {
+        let bounds = match player.pruning.star {
+            Star::Off => None,
+            _ if player.chance == ChanceModel::Worst => None,
+            _ if player.eval.counts_score() => None,
+            _ => player.eval.bounds(),
+        };
+        Self {
+            player,
+            depth_limit,
+            bounds,
+            map: TranspositionTable::new(),
+        }
+    }
+
+    /// The widest window: the evaluator bounds, or unbounded without pruning.
+    fn window(&self) -> (f32, f32) {
+        self.bounds.unwrap_or((f32::NEG_INFINITY, f32::INFINITY))
+    }
+
+    /// Tile placements after a move on `b`, each with its weight in the
+    /// expectation and its probability of happening. The two differ only when
+    /// `sample_cells` leaves some of the empty cells out.
+    fn spawns(&self, b: &Board) -> Vec<(Board, f32, f32)> {
+        let mut outcomes = vec![(*b, 1.0, 1.0)];
+        for _ in 0..self.player.rules.per_move {
+            outcomes = outcomes
+                .into_iter()
+                .flat_map(|(b, w, p)| {
+                    self.spawn_one(&b)
+                        .into_iter()
+                        .map(move |(child, cw, cp)| (child, w * cw, p * cp))
+                })
+                .collect();
+        }
+        outcomes
+    }
+
+    /// The placements of a single tile, as for `spawns`.
+    fn spawn_one(&self, b: &Board) -> Vec<(Board, f32, f32)> {
+        let rules = self.player.rules.with_per_move(1);
+        let mut cells: Vec<u8> = (0..16).filter(|&i| b.at(i) == 0).collect();
+        let k = self
+            .player
+            .pruning
+            .sample_cells
+            .map(|k| (k as usize).max(1));
+        if k.is_none_or(|k| cells.len() <= k) {
+            return b.spawn_outcomes(&rules).map(|(c, p)| (c, p, p)).collect();
+        }
+        let k = k.unwrap();
+        let cell_prob = 1.0 / cells.len() as f32;
+        let mut rng = Rng::with_seed(b.raw);
+        for i in 0..k {
+            let j = rng.usize(i..cells.len());
+            cells.swap(i, j);
+        }
+        cells.truncate(k);
+        let cell_weight = 1.0 / k as f32;
+        cells
+            .iter()
+            .flat_map(|&i| {
+                rules.tiles().map(move |(e, p)| {
+                    let mut child = *b;
+                    child.set(i, e);
+                    (child, p * cell_weight, p * cell_prob)
+                })
+            })
+            .collect()
+    }
 
     /// Computes expected value over all possible random tile placements.
+    ///
+    /// With Star pruning the result is only exact inside `(alpha, beta)`;
+    /// outside it is a bound on the same side of the window.
     fn random_player_score(
-        &mut self,
+        &self,
         b: &Board,
+        curdepth: u32,
         cprob: f32,
-        map: &mut HashMap<Board, (u32, f32)>,
+        alpha: f32,
+        beta: f32,
     ) -> f32 {
-        if cprob < Self::CPROB_THRESH_BASE || self.curdepth >= self.depth_limit {
-            self.maxdepth = self.curdepth.max(self.maxdepth);
-            return CACHE.get_score(b);
+        let cacheable = curdepth < ExpectimaxPlayer::<E>::CACHE_DEPTH_LIMIT;
+        if cprob < self.player.pruning.prob_cutoff || curdepth >= self.depth_limit {
+            return self.player.eval.evaluate(b);
         }
 
-        if self.curdepth < Self::CACHE_DEPTH_LIMIT {
-            if let Some(&(depth, score)) = map.get(b) {
-                if depth <= self.curdepth {
-                    self.cache_hits += 1;
+        if cacheable {
+            if let Some((depth, score)) = self.map.get(b) {
+                if depth <= curdepth {
                     return score;
                 }
             }
         }
 
-        let num_empty = b.num_empty() as f32;
-        let cprob = cprob / num_empty;
+        let outcomes = self.spawns(b);
+        let res = match self.bounds {
+            _ if self.player.chance == ChanceModel::Worst => {
+                match self.worst_children(&outcomes, curdepth, alpha, beta) {
+                    Ok(res) => res,
+                    Err(bound) => return bound,
+                }
+            }
+            _ if curdepth < self.player.parallel_depth => {
+                let (alpha, beta) = self.window();
+                outcomes
+                    .par_iter()
+                    .map(|&(child, w, p)| {
+                        w * self.best_move_player_score(
+                            &child,
+                            curdepth,
+                            cprob * p,
+                            alpha,
+                            beta,
+                            None,
+                        )
+                    })
+                    .sum::<f32>()
+            }
+            None => outcomes
+                .iter()
+                .map(|&(child, w, p)| {
+                    w * self.best_move_player_score(&child, curdepth, cprob * p, alpha, beta, None)
+                })
+                .sum::<f32>(),
+            Some(bounds) => {
+                match self.star_children(&outcomes, curdepth, cprob, alpha, beta, bounds) {
+                    Ok(res) => res,
+                    // a cut value is a bound, not something to remember
+                    Err(bound) => return bound,
+                }
+            }
+        };
 
-        // let mut res = 0.0;
+        if cacheable {
+            self.map.insert(*b, curdepth, res);
+        }
 
-        let res = (0..16)
-            .into_iter()
-            .filter(|&i| b.at(i) == 0)
-            .map(|i| {
-                let mut b1 = b.clone();
-                b1.set(i, 1);
-                let mut b2 = b.clone();
-                b2.set(i, 2);
-                (b1, b2)
-            })
-            .map(|(b1, b2)| {
-                let res1 = self.best_move_player_score(&b1, cprob * 0.9, map) * 0.9;
-                let res2 = self.best_move_player_score(&b2, cprob * 0.1, map) * 0.1;
-                res1 + res2
-            })
-            .sum::<f32>()
-            / num_empty;
+        res
+    }
 
-        if self.curdepth < Self::CACHE_DEPTH_LIMIT {
-            map.insert(b.clone(), (self.curdepth, res));
+    /// Star1 (and with `Star::Star2`, probing) over the outcomes of a chance
+    /// node. Returns `Err` with a bound if the node was cut.
+    fn star_children(
+        &self,
+        outcomes: &[(Board, f32, f32)],
+        curdepth: u32,
+        cprob: f32,
+        alpha: f32,
+        beta: f32,
+        (lower, upper): (f32, f32),
+    ) -> Result<f32, f32> {
+        // lower bounds on each outcome, and the probe that produced them
+        let mut probes = vec![None; outcomes.len()];
+        let mut lows = vec![lower; outcomes.len()];
+        if self.player.pruning.star == Star::Star2 {
+            for (i, &(child, _, p)) in outcomes.iter().enumerate() {
+                probes[i] = self.probe(&child, curdepth, cprob * p, lower, upper);
+                if let Some(v) = probes[i] {
+                    lows[i] = v;
+                }
+            }
+            let low: f32 = outcomes
+                .iter()
+                .zip(&lows)
+                .map(|(&(_, w, _), l)| w * l)
+                .sum();
+            if low >= beta {
+                return Err(low);
+            }
         }
 
-        res
+        let mut done = 0.0;
+        let mut rest: f32 = outcomes.iter().map(|&(_, w, _)| w).sum();
+        let mut rest_low: f32 = outcomes
+            .iter()
+            .zip(&lows)
+            .map(|(&(_, w, _), l)| w * l)
+            .sum();
+        for (i, &(child, w, p)) in outcomes.iter().enumerate() {
+            rest -= w;
+            rest_low -= w * lows[i];
+            let child_alpha = ((alpha - done - rest * upper) / w).max(lower);
+            let child_beta = ((beta - done - rest_low) / w).min(upper);
+            let v = self.best_move_player_score(
+                &child,
+                curdepth,
+                cprob * p,
+                child_alpha,
+                child_beta,
+                probes[i],
+            );
+            done += w * v;
+            if done + rest * upper <= alpha {
+                return Err(done + rest * upper);
+            }
+            if done + rest_low >= beta {
+                return Err(done + rest_low);
+            }
+        }
+        Ok(done)
+    }
+
+    /// The least valuable outcome of a chance node, searched with alpha-beta
+    /// cuts. Returns `Err` with a bound if the value fell outside the window.
+    fn worst_children(
+        &self,
+        outcomes: &[(Board, f32, f32)],
+        curdepth: u32,
+        alpha: f32,
+        beta: f32,
+    ) -> Result<f32, f32> {
+        if curdepth < self.player.parallel_depth {
+            let (alpha, beta) = self.window();
+            return Ok(outcomes
+                .par_iter()
+                .map(|&(child, _, _)| {
+                    self.best_move_player_score(&child, curdepth, 1.0, alpha, beta, None)
+                })
+                .min_by(|a, b| a.total_cmp(b))
+                .unwrap_or(f32::INFINITY));
+        }
+        let mut worst = f32::INFINITY;
+        for &(child, _, _) in outcomes {
+            let v =
+                self.best_move_player_score(&child, curdepth, 1.0, alpha, beta.min(worst), None);
+            worst = worst.min(v);
+            if worst <= alpha {
+                return Err(worst);
+            }
+        }
+        // every outcome was cut by a max node, so this is only a lower bound
+        if worst >= beta {
+            return Err(worst);
+        }
+        Ok(worst)
     }
 
+    /// Search the first legal move of `b`, which bounds its value from below.
+    fn probe(&self, b: &Board, curdepth: u32, cprob: f32, lower: f32, upper: f32) -> Option<f32> {
+        let (_, after, reward) = b.afterstates().next()?;
+        let v = self.random_player_score(&after, curdepth + 1, cprob, lower, upper);
+        Some(self.player.reward(reward) + v)
+    }
+
+    /// Value of the best move from `b`, or 0 if the game is over. `probe` is
+    /// the value of the first legal move, if already known.
     fn best_move_player_score(
-        &mut self,
+        &self,
         b: &Board,
+        curdepth: u32,
         cprob: f32,
-        map: &mut HashMap<Board, (u32, f32)>,
+        alpha: f32,
+        beta: f32,
+        probe: Option<f32>,
     ) -> f32 {
-        let mut best_score = 0.0;
-        for m in Move::all() {
-            let mut new_board = b.clone();
-            if new_board.make_move(m).is_some() {
-                self.curdepth += 1;
-                let score = self.random_player_score(&new_board, cprob, map);
-                self.curdepth -= 1;
-                if score > best_score {
-                    best_score = score;
+        let mut best_score = probe;
+        let mut alpha = alpha;
+        let skip = probe.is_some() as usize;
+        for (_, after, reward) in b.afterstates().skip(skip) {
+            if let Some(best) = best_score {
+                if best >= beta {
+                    break;
                 }
+                alpha = alpha.max(best);
+            }
+            let score = self.player.reward(reward)
+                + self.random_player_score(&after, curdepth + 1, cprob, alpha, beta);
+            if best_score.is_none_or(|best| score > best) {
+                best_score = Some(score);
             }
-            self.moves_simulated += 1;
         }
-        best_score
+        best_score.unwrap_or(0.0)
     }
 }
 
 struct HeuristicScoreCache {
     row_score_cache: [f32; 1 << 16],
+    row_min: f32,
+    row_max: f32,
 }
 
 impl HeuristicScoreCache {
-    const SCORE_LOST_PENALTY: f32 = 200000.0;
-    const SCORE_MONOTONICITY_POWER: f32 = 4.0;
-    const SCORE_MONOTONICITY_WEIGHT: f32 = 47.0;
-    const SCORE_SUM_POWER: f32 = 3.5;
-    const SCORE_SUM_WEIGHT: f32 = 11.0;
-    const SCORE_MERGES_WEIGHT: f32 = 700.0;
-    const SCORE_EMPTY_WEIGHT: f32 = 270.0;
-
-    fn new() -> Self {
+    const fn new() -> Self {
+        // written by build.rs from src/heuristic_row.rs
+        const BYTES: &[u8; 4 << 16] =
+            include_bytes!(concat!(env!("OUT_DIR"), "/heuristic_rows.bin"));
         let mut cache = [0.0; 1 << 16];
-        for i in 0..1u64 << 16 {
-            let mut line = [0 as u8; 4];
-            for j in 0..4 {
-                line[j] = ((i >> (4 * j)) & 0xf) as u8;
-            }
-            cache[i as usize] = Self::compute_row_score(&line);
+        let mut row_min = f32::INFINITY;
+        let mut row_max = f32::NEG_INFINITY;
+        let mut i = 0;
+        while i < 1 << 16 {
+            let b = 4 * i;
+            let score = f32::from_le_bytes([BYTES[b], BYTES[b + 1], BYTES[b + 2], BYTES[b + 3]]);
+            cache[i] = score;
+            row_min = row_min.min(score);
+            row_max = row_max.max(score);
+            i += 1;
         }
         Self {
             row_score_cache: cache,
+            row_min,
+            row_max,
         }
     }
 
-    fn compute_row_score(line: &[u8; 4]) -> f32 {
-        let mut sum = 0.0;
-        let mut empty = 0;
-        let mut merges = 0;
-
-        let mut prev = 0.0;
-        let mut counter = 0;
-        for i in 0..4 {
-            let rank = line[i] as f32;
-            sum += rank.powf(Self::SCORE_SUM_POWER);
-            if rank == 0.0 {
-                // count empty cells
-                empty += 1;
-            } else {
-                // count number of possible merges
-                if prev == rank {
-                    counter += 1;
-                } else if counter > 0 {
-                    merges += 1 + counter;
-                    counter = 0;
-                }
-                prev = rank;
-            }
-        }
-        if counter > 0 {
-            merges += 1 + counter;
-        }
-
-        let mut monotonicity_left = 0.0;
-        let mut monotonicity_right = 0.0;
-        for i in 1..4 {
-            let prev = line[i - 1] as f32;
-            let next = line[i] as f32;
-            if prev > next {
-                monotonicity_left += prev.powf(Self::SCORE_MONOTONICITY_POWER)
-                    - next.powf(Self::SCORE_MONOTONICITY_POWER);
-            } else {
-                monotonicity_right += next.powf(Self::SCORE_MONOTONICITY_POWER)
-                    - prev.powf(Self::SCORE_MONOTONICITY_POWER);
-            }
-        }
-
-        return Self::SCORE_LOST_PENALTY
-            + Self::SCORE_EMPTY_WEIGHT * empty as f32
-            + Self::SCORE_MERGES_WEIGHT * merges as f32
-            - Self::SCORE_MONOTONICITY_WEIGHT * monotonicity_left.min(monotonicity_right)
-            - Self::SCORE_SUM_WEIGHT * sum;
+    fn bounds(&self) -> (f32, f32) {
+        board_bounds(self.row_min, self.row_max)
     }
 
     fn get_score(&self, b: &Board) -> f32 {
-        let mut b_trans = b.clone();
-        b_trans.transpose();
-        let b_trans = b_trans;
+        rows_score(&self.row_score_cache, b)
+    }
+}
 
-        let mut score = 0.0;
-        for row in 0..4 {
-            score += self.row_score_cache[b.get_row(row).raw as usize];
-            score += self.row_score_cache[b_trans.get_row(row).raw as usize];
-        }
-        score
+/// Bounds on `rows_score` for a table with the given extremes, widened to
+/// include the 0 of a lost game.
+fn board_bounds(row_min: f32, row_max: f32) -> (f32, f32) {
+    ((8.0 * row_min).min(0.0), (8.0 * row_max).max(0.0))
+}
+
+/// The sum of the table's scores for the rows and columns of `b`.
+fn rows_score(rows: &[f32], b: &Board) -> f32 {
+    let mut b_trans = *b;
+    b_trans.transpose();
+    let b_trans = b_trans;
+
+    let mut score = 0.0;
+    for row in 0..4 {
+        score += rows[b.get_row(row).raw as usize];
+        score += rows[b_trans.get_row(row).raw as usize];
     }
+    score
 }
@@ -0,0 +1,274 @@
+use crate::{Board, Move, SpawnRules};
+use fastrand::Rng;
+use std::fmt;
+use std::hash::Hash;
+use std::vec;
+
+/// The rules of 2048 on an N x N board of tile exponents (0 = empty), with
+/// cells numbered row-major from the top-left.
+///
+/// Only `new`, `at` and `set` are required; `make_move` works line by line
+/// through them and representations with move tables can override it.
+pub trait GameBoard: Copy + Eq + Hash + fmt::Display + Send + Sync {
+    /// Side length of the board.
+    const SIZE: u8;
+    /// Largest exponent a cell holds. Two such tiles don't merge.
+    const MAX_EXPONENT: u8;
+
+    fn new() -> Self;
+    fn at(&self, i: u8) -> u8;
+    fn set(&mut self, i: u8, val: u8);
+
+    fn cells() -> u8 {
+        Self::SIZE * Self::SIZE
+    }
+
+    fn get(&self, i: u8, j: u8) -> u8 {
+        self.at(i * Self::SIZE + j)
+    }
+
+    /// Slide in direction `m`, returning the merge score if anything moved.
+    fn make_move(&mut self, m: Move) -> Option<u32> {
+        let n = Self::SIZE;
+        let mut score = 0;
+        let mut did_move = false;
+        let mut line = [0; u8::MAX as usize];
+        for l in 0..n {
+            let line = &mut line[..n as usize];
+            let cell = |k: u8| line_cell(n, m, l, k);
+            for k in 0..n {
+                line[k as usize] = self.at(cell(k));
+            }
+            let (delta, moved) = slide_line(line, Self::MAX_EXPONENT);
+            if moved {
+                did_move = true;
+                for k in 0..n {
+                    self.set(cell(k), line[k as usize]);
+                }
+            }
+            score += delta;
+        }
+        if did_move {
+            Some(score)
+        } else {
+            None
+        }
+    }
+
+    /// Each legal move with the board it leads to, before any tile spawns,
+    /// and the merge score it earns. Moves come in the order of `Move::all`.
+    fn afterstates(&self) -> impl Iterator<Item = (Move, Self, u32)> {
+        let b = *self;
+        Move::all().into_iter().filter_map(move |m| {
+            let mut after = b;
+            let reward = after.make_move(m)?;
+            Some((m, after, reward))
+        })
+    }
+
+    /// Every way the tiles of one turn can spawn on this afterstate under
+    /// `rules`, with its probability. Placements that reach the same board by
+    /// different routes are listed separately. A full board yields itself.
+    fn spawn_outcomes(&self, rules: &SpawnRules) -> vec::IntoIter<(Self, f32)> {
+        let mut outcomes = vec![(*self, 1.0)];
+        for _ in 0..rules.per_move {
+            outcomes = outcomes
+                .into_iter()
+                .flat_map(|(b, p)| {
+                    let empty: Vec<u8> = (0..Self::cells()).filter(|&i| b.at(i) == 0).collect();
+                    if empty.is_empty() {
+                        // later tiles of a turn have nowhere to go
+                        return vec![(b, p)];
+                    }
+                    let cell_prob = p / empty.len() as f32;
+                    empty
+                        .iter()
+                        .flat_map(|&i| {
+                            rules.tiles().map(move |(e, tile_prob)| {
+                                let mut child = b;
+                                child.set(i, e);
+                                (child, cell_prob * tile_prob)
+                            })
+                        })
+                        .collect()
+                })
+                .collect();
+        }
+        outcomes.into_iter()
+    }
+
+    fn game_ended(&self) -> bool {
+        Move::all().iter().all(|&m| {
+            let mut b = *self;
+            b.make_move(m).is_none()
+        })
+    }
+
+    fn num_empty(&self) -> u8 {
+        (0..Self::cells()).filter(|&i| self.at(i) == 0).count() as u8
+    }
+
+    fn log_max_tile(&self) -> u8 {
+        (0..Self::cells()).map(|i| self.at(i)).max().unwrap()
+    }
+
+    fn max_tile(&self) -> u32 {
+        1u32 << self.log_max_tile()
+    }
+
+    fn sum_tile(&self) -> u32 {
+        (0..Self::cells()).map(|i| 1u32 << self.at(i)).sum()
+    }
+
+    fn add_random_tile(&mut self, rng: &mut Rng) {
+        self.add_random_tile_with(&SpawnRules::CLASSIC, rng);
+    }
+
+    /// Add one tile drawn from `rules` to a random empty cell.
+    fn add_random_tile_with(&mut self, rules: &SpawnRules, rng: &mut Rng) {
+        let empty: Vec<u8> = (0..Self::cells()).filter(|&i| self.at(i) == 0).collect();
+        if !empty.is_empty() {
+            self.set(empty[rng.usize(0..empty.len())], rules.sample(rng));
+        }
+    }
+
+    /// Add the tiles that follow a move under `rules`.
+    fn spawn(&mut self, rules: &SpawnRules, rng: &mut Rng) {
+        for _ in 0..rules.per_move {
+            self.add_random_tile_with(rules, rng);
+        }
+    }
+}
+
+/// Index of the `k`th cell of line `l` counted from the edge that tiles
+/// slide towards under `m`.
+pub(crate) fn line_cell(n: u8, m: Move, l: u8, k: u8) -> u8 {
+    match m {
+        Move::Left => l * n + k,
+        Move::Right => l * n + (n - 1 - k),
+        Move::Up => k * n + l,
+        Move::Down => (n - 1 - k) * n + l,
+    }
+}
+
+/// Slide and merge `line` towards index 0, returning the score and whether
+/// anything changed. Tiles at `max_exponent` don't merge.
+pub(crate) fn slide_line(line: &mut [u8], max_exponent: u8) -> (u32, bool) {
+    let mut score = 0;
+    let mut moved = false;
+    let mut top = 0;
+    // tile waiting for a partner, and where it came from
+    let mut tmp = 0;
+    let mut tmp_from = 0;
+    for i in 0..line.len() {
+        let tile = line[i];
+        if tile == 0 {
+            continue;
+        }
+        line[i] = 0;
+        if tmp == 0 {
+            tmp = tile;
+            tmp_from = i;
+            continue;
+        }
+        if tile == tmp && tile < max_exponent {
+            line[top] = tile + 1;
+            score += 1u32 << (tile + 1);
+            moved = true;
+            tmp = 0;
+        } else {
+            line[top] = tmp;
+            moved |= top != tmp_from;
+            tmp = tile;
+            tmp_from = i;
+        }
+        top += 1;
+    }
+    if tmp != 0 {
+        line[top] = tmp;
+        moved |= top != tmp_from;
+    }
+    (score, moved)
+}
+
+impl GameBoard for Board {
+    const SIZE: u8 = 4;
+    const MAX_EXPONENT: u8 = Board::MAX_EXPONENT;
+
+    fn new() -> Self {
+        Board::new()
+    }
+
+    fn at(&self, i: u8) -> u8 {
+        Board::at(self, i)
+    }
+
+    fn set(&mut self, i: u8, val: u8) {
+        Board::set(self, i, val)
+    }
+
+    fn make_move(&mut self, m: Move) -> Option<u32> {
+        Board::make_move(self, m)
+    }
+
+    fn game_ended(&self) -> bool {
+        Board::game_ended(self)
+    }
+
+    fn add_random_tile_with(&mut self, rules: &SpawnRules, rng: &mut Rng) {
+        Board::add_random_tile_with(self, rules, rng)
+    }
+}
+
+/// An N x N board packed a nibble per cell into a `u128`, for N up to 5.
+/// Tiles stop at 32768, like `Board`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct PackedBoard<const N: u8> {
+    pub raw: u128,
+}
+
+pub type Board3 = PackedBoard<3>;
+pub type Board5 = PackedBoard<5>;
+
+impl<const N: u8> GameBoard for PackedBoard<N> {
+    const SIZE: u8 = {
+        assert!(
+            N as u32 * N as u32 * 4 <= 128,
+            "PackedBoard holds at most 5x5"
+        );
+        N
+    };
+    const MAX_EXPONENT: u8 = 15;
+
+    fn new() -> Self {
+        Self { raw: 0 }
+    }
+
+    fn at(&self, i: u8) -> u8 {
+        ((self.raw >> (i << 2)) & 0x0f) as u8
+    }
+
+    fn set(&mut self, i: u8, val: u8) {
+        self.raw = (self.raw & !(0x0f << (i << 2))) | (((val & 0x0f) as u128) << (i << 2));
+    }
+}
+
+impl<const N: u8> fmt::Display for PackedBoard<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_tiles(self, f)
+    }
+}
+
+/// Print the tile values of `b` a row per line, like `Board`'s `Display`.
+pub(crate) fn fmt_tiles<B: GameBoard>(b: &B, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut board_str = String::new();
+    for y in 0..B::SIZE {
+        for x in 0..B::SIZE {
+            let value = b.get(y, x);
+            board_str.push_str(&format!("{} ", if value == 0 { 0 } else { 1u64 << value }));
+        }
+        board_str.pop(); // Remove the last space
+        board_str.push('\n'); // Newline after each row
+    }
+    write!(f, "{}", board_str)
+}
@@ -0,0 +1,302 @@
+//! 2048 as a reinforcement-learning environment in the style of OpenAI Gym,
+//! so agents share one implementation of the game loop.
+//!
+//! Actions are moves, numbered as by `Move::to_int` where a number is
+//! needed, e.g. in legal-action masks. The reward of a step is its merge
+//! score. Observations are flat `f32` buffers in an `Encoding`.
+use crate::{Board, GameBoard, Move, SpawnRules};
+use fastrand::Rng;
+
+/// How a board is laid out as an observation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// A cell's exponent per cell, in reading order, 0 for empty.
+    #[default]
+    Exponents,
+    /// A row of `MAX_EXPONENT + 1` flags per cell, in reading order, with the
+    /// flag of the cell's exponent set: 16x16 for a `Board`.
+    OneHot,
+}
+
+impl Encoding {
+    /// Length of an observation of a `B`.
+    pub fn size<B: GameBoard>(self) -> usize {
+        let cells = B::cells() as usize;
+        match self {
+            Encoding::Exponents => cells,
+            Encoding::OneHot => cells * (B::MAX_EXPONENT as usize + 1),
+        }
+    }
+
+    /// Write `b` into `out`, which is `size` long.
+    pub fn encode<B: GameBoard>(self, b: &B, out: &mut [f32]) {
+        assert_eq!(out.len(), self.size::<B>(), "observation buffer length");
+        match self {
+            Encoding::Exponents => {
+                for (i, o) in (0..B::cells()).zip(out) {
+                    *o = b.at(i) as f32;
+                }
+            }
+            Encoding::OneHot => {
+                out.fill(0.0);
+                let channels = B::MAX_EXPONENT as usize + 1;
+                for i in 0..B::cells() {
+                    out[i as usize * channels + b.at(i) as usize] = 1.0;
+                }
+            }
+        }
+    }
+}
+
+/// What a step did besides its reward.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepInfo<B: GameBoard = Board> {
+    /// The board after the move, before tiles spawned.
+    pub afterstate: B,
+    /// False if the move changed nothing, in which case nothing spawned.
+    pub legal: bool,
+    /// Score of the game so far.
+    pub score: u32,
+    /// Legal moves made in the game so far.
+    pub moves: u32,
+}
+
+/// The result of `Env::step`: Gym's `(observation, reward, done, info)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step<B: GameBoard = Board> {
+    pub obs: Vec<f32>,
+    pub reward: f32,
+    /// No move is left.
+    pub done: bool,
+    pub info: StepInfo<B>,
+}
+
+/// One game of 2048. Call `reset` to start a game before stepping.
+pub struct Env<B: GameBoard = Board> {
+    board: B,
+    rules: SpawnRules,
+    encoding: Encoding,
+    rng: Rng,
+    score: u32,
+    moves: u32,
+}
+
+impl<B: GameBoard> Default for Env<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: GameBoard> Env<B> {
+    pub fn new() -> Self {
+        Self {
+            board: B::new(),
+            rules: SpawnRules::CLASSIC,
+            encoding: Encoding::default(),
+            rng: Rng::new(),
+            score: 0,
+            moves: 0,
+        }
+    }
+
+    pub fn with_rules(mut self, rules: SpawnRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Start a new game, with its spawns drawn from `seed` if given, and
+    /// where the last game's left off otherwise.
+    pub fn reset(&mut self, seed: Option<u64>) -> Vec<f32> {
+        self.start(seed);
+        self.observation()
+    }
+
+    /// Make move `m`. A move that changes nothing is allowed, earns
+    /// nothing, and leaves the game as it was.
+    pub fn step(&mut self, m: Move) -> Step<B> {
+        let (reward, done, info) = self.advance(m);
+        Step {
+            obs: self.observation(),
+            reward,
+            done,
+            info,
+        }
+    }
+
+    /// The legal moves, indexed by `Move::to_int`.
+    pub fn legal_mask(&self) -> [bool; 4] {
+        let mut mask = [false; 4];
+        for (m, _, _) in self.board.afterstates() {
+            mask[m.to_int() as usize] = true;
+        }
+        mask
+    }
+
+    pub fn observation(&self) -> Vec<f32> {
+        let mut obs = vec![0.0; self.obs_len()];
+        self.observe(&mut obs);
+        obs
+    }
+
+    /// Write the observation into `out`, which is `obs_len` long.
+    pub fn observe(&self, out: &mut [f32]) {
+        self.encoding.encode(&self.board, out);
+    }
+
+    pub fn obs_len(&self) -> usize {
+        self.encoding.size::<B>()
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn board(&self) -> &B {
+        &self.board
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    pub fn moves(&self) -> u32 {
+        self.moves
+    }
+
+    pub fn done(&self) -> bool {
+        self.board.game_ended()
+    }
+
+    fn start(&mut self, seed: Option<u64>) {
+        if let Some(seed) = seed {
+            self.rng.seed(seed);
+        }
+        self.board = B::new();
+        for _ in 0..self.rules.initial {
+            self.board.add_random_tile_with(&self.rules, &mut self.rng);
+        }
+        self.score = 0;
+        self.moves = 0;
+    }
+
+    /// `step` without the observation.
+    fn advance(&mut self, m: Move) -> (f32, bool, StepInfo<B>) {
+        let mut afterstate = self.board;
+        let reward = afterstate.make_move(m);
+        if let Some(reward) = reward {
+            self.board = afterstate;
+            self.board.spawn(&self.rules, &mut self.rng);
+            self.score += reward;
+            self.moves += 1;
+        }
+        let info = StepInfo {
+            afterstate,
+            legal: reward.is_some(),
+            score: self.score,
+            moves: self.moves,
+        };
+        (reward.unwrap_or(0) as f32, self.done(), info)
+    }
+}
+
+/// The result of `VecEnv::step`, an entry per environment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VecStep<B: GameBoard = Board> {
+    /// The observations back to back.
+    pub obs: Vec<f32>,
+    pub rewards: Vec<f32>,
+    pub dones: Vec<bool>,
+    /// For an environment that finished, the info of its last step.
+    pub infos: Vec<StepInfo<B>>,
+}
+
+/// Several games stepped together. A game that ends is reset straight away,
+/// so its observation is the next game's first; its `done` and info tell
+/// how the last one ended.
+pub struct VecEnv<B: GameBoard = Board> {
+    envs: Vec<Env<B>>,
+}
+
+impl<B: GameBoard> VecEnv<B> {
+    /// `n` environments observed in `encoding`.
+    pub fn new(n: usize, encoding: Encoding) -> Self {
+        Self::from_envs((0..n).map(|_| Env::new().with_encoding(encoding)).collect())
+    }
+
+    /// Step `envs` together. They must share an encoding.
+    pub fn from_envs(envs: Vec<Env<B>>) -> Self {
+        assert!(
+            envs.windows(2).all(|w| w[0].encoding == w[1].encoding),
+            "environments observed differently"
+        );
+        Self { envs }
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    pub fn envs(&self) -> &[Env<B>] {
+        &self.envs
+    }
+
+    /// Length of one environment's observation.
+    pub fn obs_len(&self) -> usize {
+        self.envs.first().map_or(0, Env::obs_len)
+    }
+
+    /// Start every game, the `i`th with `seed + i` if given.
+    pub fn reset(&mut self, seed: Option<u64>) -> Vec<f32> {
+        for (i, env) in self.envs.iter_mut().enumerate() {
+            env.start(seed.map(|s| s.wrapping_add(i as u64)));
+        }
+        self.observations()
+    }
+
+    /// Make `moves[i]` in the `i`th game.
+    pub fn step(&mut self, moves: &[Move]) -> VecStep<B> {
+        assert_eq!(moves.len(), self.envs.len(), "a move per environment");
+        let mut rewards = Vec::with_capacity(moves.len());
+        let mut dones = Vec::with_capacity(moves.len());
+        let mut infos = Vec::with_capacity(moves.len());
+        for (env, &m) in self.envs.iter_mut().zip(moves) {
+            let (reward, done, info) = env.advance(m);
+            if done {
+                env.start(None);
+            }
+            rewards.push(reward);
+            dones.push(done);
+            infos.push(info);
+        }
+        VecStep {
+            obs: self.observations(),
+            rewards,
+            dones,
+            infos,
+        }
+    }
+
+    /// Each game's legal moves, indexed by `Move::to_int`.
+    pub fn legal_masks(&self) -> Vec<[bool; 4]> {
+        self.envs.iter().map(Env::legal_mask).collect()
+    }
+
+    /// The observations back to back.
+    pub fn observations(&self) -> Vec<f32> {
+        let len = self.obs_len();
+        let mut obs = vec![0.0; len * self.envs.len()];
+        for (env, out) in self.envs.iter().zip(obs.chunks_mut(len.max(1))) {
+            env.observe(out);
+        }
+        obs
+    }
+}
@@ -0,0 +1,82 @@
+//! The expectimax heuristic for a single row. It needs `powf`, which isn't
+//! available in const fns, so `build.rs` also compiles this file and
+//! tabulates it for every row with the default weights ahead of time.
+
+/// The weights of the row heuristic. A row starts at `lost_penalty`, gains
+/// the weights for each empty cell and possible merge, and loses the weights
+/// for its non-monotonicity and tile sum, both of which add up exponents
+/// raised to their powers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeuristicParams {
+    pub lost_penalty: f32,
+    pub monotonicity_power: f32,
+    pub monotonicity_weight: f32,
+    pub sum_power: f32,
+    pub sum_weight: f32,
+    pub merges_weight: f32,
+    pub empty_weight: f32,
+}
+
+impl HeuristicParams {
+    /// The weights `Heuristic` was tuned with.
+    pub const DEFAULT: Self = Self {
+        lost_penalty: 200000.0,
+        monotonicity_power: 4.0,
+        monotonicity_weight: 47.0,
+        sum_power: 3.5,
+        sum_weight: 11.0,
+        merges_weight: 700.0,
+        empty_weight: 270.0,
+    };
+}
+
+impl Default for HeuristicParams {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+pub fn compute_row_score(line: &[u8; 4], p: &HeuristicParams) -> f32 {
+    let mut sum = 0.0;
+    let mut empty = 0;
+    let mut merges = 0;
+
+    let mut prev = 0.0;
+    let mut counter = 0;
+    for &tile in line {
+        let rank = tile as f32;
+        sum += rank.powf(p.sum_power);
+        if rank == 0.0 {
+            // count empty cells
+            empty += 1;
+        } else {
+            // count number of possible merges
+            if prev == rank {
+                counter += 1;
+            } else if counter > 0 {
+                merges += 1 + counter;
+                counter = 0;
+            }
+            prev = rank;
+        }
+    }
+    if counter > 0 {
+        merges += 1 + counter;
+    }
+
+    let mut monotonicity_left = 0.0;
+    let mut monotonicity_right = 0.0;
+    for i in 1..4 {
+        let prev = line[i - 1] as f32;
+        let next = line[i] as f32;
+        if prev > next {
+            monotonicity_left += prev.powf(p.monotonicity_power) - next.powf(p.monotonicity_power);
+        } else {
+            monotonicity_right += next.powf(p.monotonicity_power) - prev.powf(p.monotonicity_power);
+        }
+    }
+
+    p.lost_penalty + p.empty_weight * empty as f32 + p.merges_weight * merges as f32
+        - p.monotonicity_weight * monotonicity_left.min(monotonicity_right)
+        - p.sum_weight * sum
+}
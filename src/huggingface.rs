@@ -1,13 +1,32 @@
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::io::{self, Read};
 
 use reqwest::blocking::Client;
 
+use crate::Fetch;
+
 pub fn download_huggingface_model(url: &str) -> Vec<u8> {
     let client = Client::new();
-    let mut response = client.get(&url).send().unwrap();
+    let mut response = client.get(url).send().unwrap();
     let mut buffer = Vec::new();
     response.read_to_end(&mut buffer).unwrap();
     buffer
 }
+
+/// Fetches weight files over HTTP(S) for a `Registry`, e.g. from Hugging Face.
+#[derive(Default)]
+pub struct HttpFetch {
+    client: Client,
+}
+
+impl Fetch for HttpFetch {
+    fn fetch(&self, url: &str) -> io::Result<Vec<u8>> {
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .and_then(|r| r.error_for_status())
+            .map_err(io::Error::other)?;
+        let bytes = response.bytes().map_err(io::Error::other)?;
+        Ok(bytes.to_vec())
+    }
+}
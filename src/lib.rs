@@ -1,19 +1,74 @@
+mod array_board;
 mod board;
+mod config;
+mod engine;
 mod expectimax;
+mod game_board;
+mod gym;
+mod heuristic_row;
+#[cfg(feature = "huggingface")]
+pub mod huggingface;
 mod monte_carlo;
+mod notation;
 mod ntuple;
+mod ntuple_format;
+mod par;
 mod player;
-pub use board::{Board, Move};
-pub use expectimax::ExpectimaxPlayer;
+#[cfg(feature = "python")]
+mod python;
+mod record;
+mod registry;
+mod row;
+#[cfg(feature = "server")]
+mod server;
+mod spawn;
+mod wasm_game;
+#[cfg(feature = "wasm")]
+mod wasm_player;
+mod web_state;
+pub use array_board::{ArrayBoard, Board6, WideBoard};
+pub use board::{Board, BoardError, Move, MoveSet};
+pub use config::{ExpectimaxConfig, MonteCarloConfig, MoveChoice, PlayerConfig};
+pub use engine::{Engine, ENGINE_PLAYERS};
+pub use expectimax::{
+    ChanceModel, ChancePruning, ConfigHeuristic, DepthPolicy, Evaluator, ExpectimaxPlayer,
+    Heuristic, Star, TunedHeuristic,
+};
 use fastrand::Rng;
+pub use game_board::{Board3, Board5, GameBoard, PackedBoard};
+pub use gym::{Encoding, Env, Step, StepInfo, VecEnv, VecStep};
+pub use heuristic_row::HeuristicParams;
 pub use monte_carlo::{MonteCarloMetric, MonteCarloPlayer};
+pub use notation::ParseError;
 pub use ntuple::{Feature, MoveRecord, NTuple};
+pub use ntuple_format::{CellOrder, Container, IndexOrder, WeightFormat};
 pub use player::Player;
+pub use record::{GameRecord, ReplayError, Spawn, Turn};
+pub use registry::{sha256_hex, Fetch, Manifest, ModelEntry, Offline, Registry};
+#[cfg(feature = "server")]
+pub use server::{ServerLimits, SolverServer};
+pub use spawn::{AdversarialSpawner, RandomSpawner, SpawnRules, Spawner};
+#[cfg(feature = "training")]
 use std::fs::File;
+#[cfg(feature = "training")]
 use std::io::BufWriter;
-use wasm_bindgen::prelude::*;
-// pub use wasm_bindgen_rayon::init_thread_pool;
+#[cfg(all(target_arch = "wasm32", feature = "wasm-threads"))]
+pub use wasm_bindgen_rayon::init_thread_pool;
+pub use wasm_game::{tile_moves, TileMove, WasmGame};
+#[cfg(feature = "wasm")]
+pub use wasm_player::{
+    build_ntuple, expectimax, monte_carlo, ntuple, random_available_move, solver_threads,
+    WasmExpectimax, WasmMonteCarlo,
+};
+pub use web_state::{WebGrid, WebPosition, WebState, WebStateError, WebTile};
 
+// wee_alloc is small but slow, and has no locking for threaded builds, so
+// only single-threaded wasm uses it
+#[cfg(all(
+    target_arch = "wasm32",
+    feature = "wasm",
+    not(feature = "wasm-threads")
+))]
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
@@ -22,6 +77,7 @@ mod tests {
     use super::*;
     use board::*;
 
+    #[cfg(feature = "cli")]
     #[ignore]
     #[test]
     fn monte_carlo() {
@@ -201,15 +257,1094 @@ mod tests {
         println!("final board: {b}");
         assert_eq!(b.raw, exp_raw);
     }
+
+    #[test]
+    fn column_moves_match_rotations() {
+        let mut rng = Rng::with_seed(7);
+        for _ in 0..1000 {
+            let b = Board::from_raw(rng.u64(..) & 0x3333_3333_3333_3333);
+            let mut up = b;
+            let mut rotated = b;
+            rotated.counterclockwise();
+            let score = rotated.move_left();
+            rotated.clockwise();
+            assert_eq!(up.move_up(), score);
+            assert_eq!(up.raw, rotated.raw);
+
+            let mut down = b;
+            let mut rotated = b;
+            rotated.clockwise();
+            let score = rotated.move_left();
+            rotated.counterclockwise();
+            assert_eq!(down.move_down(), score);
+            assert_eq!(down.raw, rotated.raw);
+        }
+    }
+
+    #[test]
+    fn legal_moves() {
+        let mut rng = Rng::with_seed(11);
+        for _ in 0..1000 {
+            let b = Board::from_raw(rng.u64(..) & 0x3333_3333_3333_3333);
+            let legal = b.legal_moves();
+            for m in Move::all() {
+                assert_eq!(legal.contains(m), b.clone().make_move(m).is_some());
+            }
+            assert_eq!(b.game_ended(), legal.is_empty());
+        }
+        // a full board without merges
+        let stuck = Board::from_raw(0x1212_2121_1212_2121);
+        assert!(stuck.legal_moves().is_empty());
+        let b = Board::from_raw(0x0000_0000_0000_0001);
+        let moves: Vec<i32> = b.legal_moves().iter().map(|m| m.to_int()).collect();
+        assert_eq!(moves, [Move::Down.to_int(), Move::Right.to_int()]);
+    }
+
+    #[test]
+    fn board_does_not_wrap_32768() {
+        let mut b = Board::new();
+        b.set(0, 15);
+        b.set(1, 15);
+        b.set(2, 14);
+        b.set(3, 14);
+        assert_eq!(b.make_move(Move::Left), Some(32768));
+        assert_eq!((b.at(0), b.at(1), b.at(2)), (15, 15, 15));
+    }
+
+    #[test]
+    fn wide_board_merges_past_32768() {
+        let mut b = WideBoard::from_exponents([15, 15, 16, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(b.make_move(Move::Left), Some(65536));
+        assert_eq!((b.at(0), b.at(1)), (16, 16));
+        assert_eq!(b.make_move(Move::Right), Some(131072));
+        assert_eq!(b.at(3), 17);
+        assert_eq!(b.max_tile(), 131072);
+        assert!(b.to_board().is_none());
+        assert_eq!(b.to_board_saturating().at(3), 15);
+        assert_eq!(format!("{b}").lines().next(), Some("0 0 0 131072"));
+    }
+
+    #[test]
+    fn referee_merges_32768s_players_cannot_see() {
+        /// Places the tiles it was given, in order.
+        struct Scripted(SpawnRules, Vec<(u8, u8)>);
+
+        impl Spawner<WideBoard> for Scripted {
+            fn rules(&self) -> &SpawnRules {
+                &self.0
+            }
+
+            fn place(&mut self, _: &WideBoard) -> (u8, u8) {
+                self.1.remove(0)
+            }
+        }
+
+        // 32768 32768 8 16 / 8 16 32 64 / 32 64 128 256 / 128 256 512 1024:
+        // the only move is a merge of the 32768s
+        let opening = [15, 15, 3, 4, 3, 4, 5, 6, 5, 6, 7, 8, 7, 8, 9, 10];
+        let mut script: Vec<(u8, u8)> = (0..16).zip(opening).collect();
+        script.push((3, 1));
+        let mut spawner = Scripted(SpawnRules::CLASSIC.with_initial(16), script);
+        let player = ExpectimaxPlayer::default().with_depth(1);
+        let mut seen = Board::new();
+        for (i, &e) in opening.iter().enumerate() {
+            seen.set(i as u8, e);
+        }
+        assert!(player.next_move(&seen).is_none());
+        assert_eq!(
+            play_against(&player, &mut spawner, 10, false),
+            (65536, 65536)
+        );
+    }
+
+    #[test]
+    fn wide_board_matches_board() {
+        let mut rng = Rng::with_seed(3);
+        let mut b = Board::new();
+        let mut w = WideBoard::from(b);
+        for _ in 0..200 {
+            let m = Move::rand(&mut rng);
+            assert_eq!(b.make_move(m), w.make_move(m));
+            assert_eq!(w.to_board(), Some(b));
+            let mut tile_rng = rng.clone();
+            b.add_random_tile(&mut rng);
+            w.add_random_tile(&mut tile_rng);
+        }
+    }
+
+    #[test]
+    fn packed_board_matches_board() {
+        let mut rng = Rng::with_seed(11);
+        let mut b = Board::new();
+        let mut p = PackedBoard::<4>::new();
+        for _ in 0..300 {
+            let m = Move::rand(&mut rng);
+            assert_eq!(b.make_move(m), GameBoard::make_move(&mut p, m));
+            assert_eq!(b.raw as u128, p.raw);
+            let mut tile_rng = rng.clone();
+            b.add_random_tile(&mut rng);
+            GameBoard::add_random_tile(&mut p, &mut tile_rng);
+        }
+    }
+
+    #[test]
+    fn other_board_sizes() {
+        let mut b = Board5::new();
+        for i in 0..5 {
+            b.set(i, 1);
+        }
+        assert_eq!(b.make_move(Move::Right), Some(8));
+        assert_eq!(format!("{b}").lines().next(), Some("0 0 2 4 4"));
+        assert_eq!(b.make_move(Move::Down), Some(0));
+        assert_eq!(b.get(4, 4), 2);
+
+        let (score, max) = play_game_on::<Board3, _>(
+            &MonteCarloPlayer::new(5, MonteCarloMetric::Sum),
+            &SpawnRules::CLASSIC,
+            1000,
+            false,
+        );
+        assert!(score > 0 && max >= 8);
+        let (score, _) = play_game_on::<Board6, _>(
+            &MonteCarloPlayer::new(2, MonteCarloMetric::Sum),
+            &SpawnRules::CLASSIC,
+            50,
+            false,
+        );
+        assert!(score > 0);
+    }
+
+    #[test]
+    fn spawn_rules_variants() {
+        let mut rng = Rng::with_seed(5);
+        let rules = SpawnRules::fours_only().with_per_move(2).with_initial(3);
+        let mut b = Board::new();
+        for _ in 0..rules.initial {
+            b.add_random_tile_with(&rules, &mut rng);
+        }
+        assert_eq!(b.num_empty(), 13);
+        b.spawn(&rules, &mut rng);
+        assert_eq!(b.num_empty(), 11);
+        assert!((0..16).all(|i| b.at(i) == 0 || b.at(i) == 2));
+
+        let hard = SpawnRules::hard();
+        let total: f32 = hard.tiles().map(|(_, p)| p).sum();
+        assert!((total - 1.0).abs() < 1e-6);
+        assert!((0..1000).all(|_| (1..=3).contains(&hard.sample(&mut rng))));
+    }
+
+    #[test]
+    fn players_follow_spawn_rules() {
+        let rules = SpawnRules::fours_only().with_per_move(2);
+        let mut b = Board::new();
+        b.set(0, 2);
+        b.set(1, 2);
+        let player = ExpectimaxPlayer::default().with_depth(1).with_rules(rules);
+        assert!(player.next_move(&b).is_some());
+        let (score, _) = play_variant(
+            &MonteCarloPlayer::new(5, MonteCarloMetric::Sum).with_rules(rules),
+            &rules,
+            100,
+            false,
+        );
+        assert!(score > 0);
+    }
+
+    fn numbered_ntuple() -> NTuple {
+        let feats = [&[0, 1, 2, 3][..], &[0, 1, 4, 5]]
+            .iter()
+            .map(|p| Feature::from_weights(p, (0..1 << 16).map(|w| w as f32).collect()))
+            .collect();
+        NTuple::new(feats)
+    }
+
+    #[test]
+    fn ntuple_native_format_matches_save_weights() {
+        let net = numbered_ntuple();
+        let mut saved = Vec::new();
+        net.save_weights(&mut saved);
+        let mut exported = Vec::new();
+        net.export(&mut exported, &WeightFormat::native()).unwrap();
+        assert_eq!(saved, exported);
+    }
+
+    #[test]
+    fn ntuple_foreign_format_roundtrip() {
+        let net = numbered_ntuple();
+        let b = Board::from_raw(0x0123_4567_89ab_cdef);
+        let format = WeightFormat::tdl2048(4)
+            .with_cells(CellOrder::column_major())
+            .with_index(IndexOrder::HighFirst);
+        let mut bytes = Vec::new();
+        net.export(&mut bytes, &format).unwrap();
+        let imported = NTuple::import(&mut bytes.as_slice(), &format).unwrap();
+        assert_eq!(imported.features()[0].pattern(), &[0, 1, 2, 3]);
+        assert_eq!(imported.estimate(&b), net.estimate(&b));
+    }
+
+    #[test]
+    fn ntuple_raw_format_remaps_cells() {
+        // A 2-tuple over the first two cells of a column-major codebase is our
+        // cells 0 and 4; written high-first it indexes as [4, 0] for us.
+        let weights: Vec<f32> = (0..256).map(|w| w as f32).collect();
+        let bytes: Vec<u8> = weights.iter().flat_map(|w| w.to_le_bytes()).collect();
+        let format = WeightFormat::raw(&[&[0, 1]])
+            .with_cells(CellOrder::column_major())
+            .with_index(IndexOrder::HighFirst);
+        let net = NTuple::import(&mut bytes.as_slice(), &format).unwrap();
+        assert_eq!(net.features()[0].pattern(), &[4, 0]);
+
+        let mut truncated = &bytes[..100];
+        assert!(NTuple::import(&mut truncated, &format).is_err());
+    }
+
+    #[test]
+    fn afterstates_and_spawn_outcomes() {
+        let b = Board::from_raw(0x0000_0000_0001_0011);
+        let after: Vec<_> = b.afterstates().collect();
+        assert_eq!(after.len(), 4);
+        for (m, a, reward) in after {
+            let mut c = b;
+            assert_eq!(c.make_move(m), Some(reward));
+            assert_eq!(a, c);
+        }
+
+        let outcomes: Vec<_> = b.spawn_outcomes(&SpawnRules::CLASSIC).collect();
+        assert_eq!(outcomes.len(), 13 * 2);
+        let total: f32 = outcomes.iter().map(|&(_, p)| p).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+        assert!(outcomes.iter().all(|(c, _)| c.num_empty() == 12));
+
+        let two = SpawnRules::CLASSIC.with_per_move(2);
+        let total: f32 = b.spawn_outcomes(&two).map(|(_, p)| p).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+        let full = Board::from_raw(0x1212_2121_1212_2121);
+        let outcomes: Vec<_> = full.spawn_outcomes(&two).collect();
+        assert_eq!(outcomes, [(full, 1.0)]);
+    }
+
+    #[test]
+    fn board_from_checked_cells() {
+        let mut exponents = [0; 16];
+        exponents[0] = 1;
+        exponents[15] = 15;
+        let mut tiles = [0; 16];
+        tiles[0] = 2;
+        tiles[15] = 32768;
+        let b = Board::try_from_exponents(&exponents).unwrap();
+        assert_eq!(b, Board::from_arr(&exponents));
+        assert_eq!(Board::try_from_tiles(&tiles), Ok(b));
+
+        assert_eq!(
+            Board::try_from_exponents(&exponents[..15]),
+            Err(BoardError::Length(15))
+        );
+        exponents[3] = 16;
+        assert_eq!(
+            Board::try_from_exponents(&exponents),
+            Err(BoardError::Cell { cell: 3, value: 16 })
+        );
+        exponents[3] = -1;
+        assert!(Board::try_from_exponents(&exponents).is_err());
+        for bad in [1, 3, -2, 65536] {
+            tiles[5] = bad;
+            assert_eq!(
+                Board::try_from_tiles(&tiles),
+                Err(BoardError::Cell {
+                    cell: 5,
+                    value: bad
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn board_notation() {
+        let mut b = Board::new();
+        b.set(0, 1);
+        b.set(3, 2);
+        b.set(6, 11);
+        b.set(15, 15);
+        let tiles = "2 0 0 4 / 0 0 2048 0 / 0 0 0 0 / 0 0 0 32768";
+        assert_eq!(tiles.parse::<Board>(), Ok(b));
+        assert_eq!(b.to_string().parse::<Board>(), Ok(b));
+        assert_eq!("2,.,.,4\n.,.,2048,.\n.,.,.,.\n.,.,.,32768".parse(), Ok(b));
+        assert_eq!(
+            "exp: 1 0 0 2 / 0 0 11 0 / 0 0 0 0 / 0 0 0 15".parse(),
+            Ok(b)
+        );
+        assert_eq!(format!("{b:x}"), "100200b00000000f");
+        assert_eq!("100200b00000000f".parse(), Ok(b));
+        assert_eq!(format!("0x{:x}", b.raw).parse(), Ok(b));
+
+        assert!("2 0 0 3 / 0 0 0 0 / 0 0 0 0 / 0 0 0 0"
+            .parse::<Board>()
+            .is_err());
+        assert!("2 0 0 4 / 0 0 0 0 / 0 0 0 0".parse::<Board>().is_err());
+        assert!("exp: 16 0 0 0 / 0 0 0 0 / 0 0 0 0 / 0 0 0 0"
+            .parse::<Board>()
+            .is_err());
+        assert!("100200b00000000".parse::<Board>().is_err());
+
+        let json = serde_json::to_string(&b).unwrap();
+        assert_eq!(json, "\"100200b00000000f\"");
+        assert_eq!(serde_json::from_str::<Board>(&json).unwrap(), b);
+        let json = format!("{:?}", tiles);
+        assert_eq!(serde_json::from_str::<Board>(&json).unwrap(), b);
+    }
+
+    #[test]
+    fn move_notation() {
+        for m in Move::all() {
+            assert_eq!(m.to_string().parse::<Move>().unwrap().to_int(), m.to_int());
+        }
+        for (s, m) in [
+            ("up", Move::Up),
+            ("D", Move::Down),
+            ("←", Move::Left),
+            ("Right", Move::Right),
+        ] {
+            assert_eq!(s.parse::<Move>().unwrap().to_int(), m.to_int());
+        }
+        assert!("north".parse::<Move>().is_err());
+        assert_eq!(serde_json::to_string(&Move::Left).unwrap(), "\"left\"");
+        let m: Move = serde_json::from_str("\"↓\"").unwrap();
+        assert_eq!(m.to_int(), Move::Down.to_int());
+    }
+
+    #[test]
+    fn expectimax_depth_zero_is_greedy_ntuple() {
+        let net = numbered_ntuple();
+        let player = ExpectimaxPlayer::new(&net).with_depth(0);
+        let mut rng = Rng::with_seed(7);
+        let mut b = Board::new();
+        b.add_random_tile(&mut rng);
+        b.add_random_tile(&mut rng);
+        while let Some(m) = net.next_move(&b) {
+            assert_eq!(player.next_move(&b).map(|m| m.to_int()), Some(m.to_int()));
+            b.make_move(m);
+            b.add_random_tile(&mut rng);
+        }
+        assert!(player.next_move(&b).is_none());
+    }
+
+    #[test]
+    fn expectimax_with_ntuple_leaves() {
+        let net = numbered_ntuple();
+        let b = Board::from_raw(0x0000_0000_1000_2121);
+        let leaves = ExpectimaxPlayer::new(&net).with_depth(0).move_scores(&b);
+        let heuristic = ExpectimaxPlayer::default().with_depth(0).move_scores(&b);
+        assert!(!leaves.is_empty());
+        for ((m, v), (_, h)) in leaves.into_iter().zip(heuristic) {
+            let mut after = b;
+            let reward = after.make_move(m).unwrap();
+            assert_eq!(v, reward as f32 + net.evaluate(&after));
+            assert_ne!(v, h);
+        }
+        let m = ExpectimaxPlayer::new(&net).with_depth(2).next_move(&b);
+        assert!(m.is_some());
+    }
+
+    #[test]
+    fn expectimax_star_pruning_keeps_move() {
+        // 128 4 2 0 / 256 8 2 0 / 512 32 8 2 / 16384 64 8 2
+        let b = Board::from_arr(&[7, 2, 1, 0, 8, 3, 1, 0, 9, 5, 3, 1, 14, 6, 3, 1]);
+        let plain = ExpectimaxPlayer::default().with_depth(2);
+        let expected = plain.next_move(&b).map(|m| m.to_int());
+        for star in [Star::Star1, Star::Star2] {
+            let pruning = ChancePruning {
+                star,
+                ..Default::default()
+            };
+            let pruned = plain.with_pruning(pruning).next_move(&b);
+            assert_eq!(pruned.map(|m| m.to_int()), expected);
+        }
+    }
+
+    #[test]
+    fn expectimax_parallel_depth_keeps_move() {
+        let b = Board::from_arr(&[7, 2, 1, 0, 8, 3, 1, 0, 9, 5, 3, 1, 14, 6, 3, 1]);
+        let player = ExpectimaxPlayer::default().with_depth(2);
+        // each root move on its own thread, but each searched in order
+        let sequential = player.with_parallel_depth(0);
+        let expected = sequential.move_scores(&b);
+        assert_eq!(sequential.move_scores(&b), expected);
+
+        // the tasks under a root move share its table, so a value can depend
+        // on which of them cached a position first, though not by much
+        for _ in 0..4 {
+            let scores = player.with_parallel_depth(2).move_scores(&b);
+            for (&(m, v), &(pm, pv)) in expected.iter().zip(&scores) {
+                assert_eq!(m.to_int(), pm.to_int());
+                assert!((v - pv).abs() <= 1e-3 * v.abs(), "{m}: {pv} against {v}");
+            }
+            let best = |s: &[(Move, f32)]| expectimax::best_of(s).map(|(_, m)| m.to_int());
+            assert_eq!(best(&scores), best(&expected));
+        }
+    }
+
+    #[test]
+    fn depth_policy_trivial_boards() {
+        let empty = Board::new();
+        let full = Board::from_raw(0x1111_1111_1111_1111);
+        let policy = DepthPolicy::default();
+        assert_eq!(policy.depth(&empty), 3);
+        assert_eq!(policy.depth(&full), 3);
+        let policy = DepthPolicy::EmptyCells { min: 2, max: 6 };
+        assert_eq!(policy.depth(&empty), 2);
+        assert_eq!(policy.depth(&full), 6);
+        let policy = DepthPolicy::DistinctTiles { min: 5, max: 1 };
+        assert_eq!(policy.depth(&empty), 5);
+    }
+
+    #[test]
+    fn expectimax_empty_board() {
+        assert!(ExpectimaxPlayer::default()
+            .next_move(&Board::new())
+            .is_none());
+        let mut b = Board::new();
+        b.set(0, 1);
+        assert!(ExpectimaxPlayer::default().next_move(&b).is_some());
+    }
+
+    #[test]
+    fn expectimax_time_budget() {
+        let b = Board::from_arr(&[7, 2, 1, 0, 8, 3, 1, 0, 9, 5, 3, 1, 14, 6, 3, 1]);
+        let policy = DepthPolicy::Time {
+            budget: Duration::from_millis(50),
+            max: 20,
+        };
+        let start = Instant::now();
+        let m = ExpectimaxPlayer::default()
+            .with_depth_policy(policy)
+            .next_move(&b);
+        assert!(m.is_some());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn expectimax_sampled_cells() {
+        let mut b = Board::new();
+        b.set(0, 1);
+        b.set(5, 1);
+        let pruning = ChancePruning {
+            sample_cells: Some(2),
+            ..Default::default()
+        };
+        let player = ExpectimaxPlayer::default().with_pruning(pruning);
+        assert!(player.next_move(&b).is_some());
+    }
+
+    #[test]
+    fn adversary_avoids_merges() {
+        // a 2 placed next to the lone 2 would hand the player a merge
+        let b = Board::from_arr(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let mut adversary = AdversarialSpawner::default();
+        let (cell, exponent) = Spawner::<Board>::place(&mut adversary, &b);
+        assert_eq!(b.at(cell), 0);
+        assert!(!(exponent == 1 && (cell == 1 || cell == 4)));
+    }
+
+    #[test]
+    fn minimax_is_pessimistic() {
+        let mut b = Board::new();
+        b.set(0, 3);
+        b.set(1, 1);
+        b.set(4, 2);
+        let expected = ExpectimaxPlayer::default().with_depth(2);
+        let minimax = ExpectimaxPlayer::minimax(Heuristic).with_depth(2);
+        let (e, _) = expected.search(&b, 2).unwrap();
+        let (w, _) = minimax.search(&b, 2).unwrap();
+        assert!(w <= e);
+        assert!(minimax.next_move(&b).is_some());
+    }
+
+    #[test]
+    fn play_against_adversary() {
+        let player = ExpectimaxPlayer::default().with_depth(1);
+        let mut adversary = AdversarialSpawner::default();
+        let (score, max_tile) = play_against(&player, &mut adversary, 50, false);
+        assert!(max_tile >= 4);
+        assert!(score > 0);
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let player = ExpectimaxPlayer::default().with_depth(1);
+        let record =
+            record_game(&player, &SpawnRules::CLASSIC, 42, 40).with_player("expectimax", "depth=1");
+        assert_eq!(record.start.len(), 2);
+        assert_eq!(record.turns.len(), 40);
+        assert!(record.turns.iter().all(|t| t.eval.is_some()));
+
+        let json = serde_json::to_string(&record).unwrap();
+        let parsed: GameRecord = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, record);
+        let boards = parsed.replay::<Board>().unwrap();
+        assert_eq!(boards.len(), 41);
+        let wide = parsed.replay::<WideBoard>().unwrap();
+        assert_eq!(wide.last().unwrap().to_board(), boards.last().copied());
+
+        // same seed, same player, same game
+        let again = record_game(&player, &SpawnRules::CLASSIC, 42, 40);
+        assert_eq!(again.turns, record.turns);
+
+        let mut bad = record.clone();
+        bad.score += 4;
+        assert!(matches!(
+            bad.replay::<Board>(),
+            Err(ReplayError::Score { .. })
+        ));
+        let mut bad = record.clone();
+        bad.turns[3].spawns[0].exponent = 5;
+        assert!(matches!(
+            bad.replay::<Board>(),
+            Err(ReplayError::BadSpawn { turn: 4, .. })
+        ));
+        assert!(record.replay::<Board5>().is_err());
+    }
+
+    #[test]
+    fn tile_moves_for_animation() {
+        // 2 2 4 0 slides left into 4 4 0 0
+        let b = Board::from_arr(&[1, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let moves = tile_moves(&b, Move::Left);
+        let t = |from, to, merged| TileMove { from, to, merged };
+        assert_eq!(moves, [t(0, 0, false), t(1, 0, true), t(2, 1, false)]);
+        let moves = tile_moves(&b, Move::Right);
+        assert_eq!(moves, [t(2, 3, false), t(1, 2, false), t(0, 2, true)]);
+        let moves = tile_moves(&b, Move::Down);
+        assert_eq!(moves, [t(0, 12, false), t(1, 13, false), t(2, 14, false)]);
+    }
+
+    #[test]
+    fn wasm_game() {
+        let mut game = WasmGame::new(Some(3));
+        assert_eq!(game.cells().iter().filter(|&&c| c != 0).count(), 2);
+        let start = game.board();
+        let mut played = 0;
+        while played < 20 && !game.over() {
+            let m = Move::from_int(game.legal_moves()[0] as u32);
+            assert!(game.play(m));
+            assert_eq!(game.last_spawns().len(), 2);
+            played += 1;
+        }
+        let score = game.score();
+        let board = game.board();
+        let saved = game.save();
+        let loaded = WasmGame::load(&saved).ok().unwrap();
+        assert_eq!(loaded.board(), board);
+        assert_eq!(loaded.score(), score);
+        let mut edited: serde_json::Value = serde_json::from_str(&saved).unwrap();
+        edited["score"] = (score + 4).into();
+        assert!(WasmGame::load(&edited.to_string()).is_err());
+
+        let mut game = loaded;
+        while game.undo() {}
+        assert_eq!(game.board(), start);
+        assert_eq!(game.score(), 0);
+        assert_eq!(WasmGame::new(Some(3)).board(), start);
+    }
+
+    #[test]
+    fn turn_notation() {
+        let turn: Turn = "L5190@1834.5".parse().unwrap();
+        assert_eq!(turn.mv.to_int(), Move::Left.to_int());
+        assert_eq!(
+            turn.spawns,
+            [
+                Spawn {
+                    cell: 5,
+                    exponent: 1
+                },
+                Spawn {
+                    cell: 9,
+                    exponent: 0
+                }
+            ]
+        );
+        assert_eq!(turn.eval, Some(1834.5));
+        assert_eq!(turn.to_string(), "L5190@1834.5");
+        assert_eq!("U".parse::<Turn>().unwrap().to_string(), "U");
+        assert!("X51".parse::<Turn>().is_err());
+        assert!("L5".parse::<Turn>().is_err());
+    }
+
+    #[test]
+    fn tuned_heuristic_defaults() {
+        let tuned = TunedHeuristic::new(HeuristicParams::default());
+        let mut rng = Rng::with_seed(5);
+        for _ in 0..50 {
+            let b = Board::from_raw(rng.u64(..));
+            assert_eq!(tuned.evaluate(&b), Heuristic.evaluate(&b));
+        }
+        assert_eq!(tuned.bounds(), Heuristic.bounds());
+        assert!(matches!(
+            ConfigHeuristic::new(HeuristicParams::DEFAULT),
+            ConfigHeuristic::Default(_)
+        ));
+        let params = HeuristicParams {
+            empty_weight: 500.0,
+            ..HeuristicParams::DEFAULT
+        };
+        let (a, b) = (ConfigHeuristic::new(params), TunedHeuristic::new(params));
+        let board = Board::from_raw(rng.u64(..));
+        assert_eq!(a.evaluate(&board), b.evaluate(&board));
+        assert_eq!(a.bounds(), b.bounds());
+    }
+
+    #[test]
+    fn configured_players() {
+        let b = Board::from_arr(&[1, 2, 3, 4, 0, 1, 2, 3, 0, 0, 1, 2, 0, 0, 0, 1]);
+        let config: ExpectimaxConfig =
+            serde_json::from_str(r#"{"depth": 2, "heuristic": {"empty_weight": 500}}"#).unwrap();
+        assert_eq!(config.heuristic.empty_weight, 500.0);
+        assert_eq!(
+            config.heuristic.sum_power,
+            HeuristicParams::DEFAULT.sum_power
+        );
+        let choice = config.player().choice(&b);
+        assert_eq!(
+            Some(Move::from_int(choice.mv as u32)),
+            config.player().next_move(&b)
+        );
+        let best = choice.scores[choice.mv as usize].unwrap();
+        assert!(choice.scores.iter().flatten().all(|&s| s <= best));
+        assert!(serde_json::from_str::<ExpectimaxConfig>(r#"{"deep": 2}"#).is_err());
+
+        let config: MonteCarloConfig =
+            serde_json::from_str(r#"{"rollouts": 20, "metric": "max_tile"}"#).unwrap();
+        let choice = config.player().choice(&b);
+        let legal = b.legal_moves();
+        for m in Move::all() {
+            assert_eq!(
+                choice.scores[m.to_int() as usize].is_some(),
+                legal.contains(m)
+            );
+        }
+        assert!(legal.contains(Move::from_int(choice.mv as u32)));
+        assert_eq!(serde_json::to_value(&choice).unwrap()["move"], choice.mv);
+
+        let over = Board::from_arr(&[1, 2, 1, 2, 2, 1, 2, 1, 1, 2, 1, 2, 2, 1, 2, 1]);
+        let choice = ExpectimaxConfig::default().player().choice(&over);
+        assert_eq!(choice.mv, -1);
+        assert_eq!(choice.scores, [None; 4]);
+    }
+
+    /// A weight file of `stages` one-cell networks, and its manifest entry.
+    fn registry_model(stages: u32) -> (Vec<u8>, ModelEntry) {
+        let mut bytes = Vec::new();
+        for stage in 0..stages {
+            let weights = (0..16).map(|i| (i + stage) as f32).collect();
+            let net = NTuple::new(vec![Feature::from_weights(&[0], weights)]);
+            net.export(&mut bytes, &WeightFormat::native()).unwrap();
+        }
+        let entry = ModelEntry {
+            name: "corner".to_string(),
+            url: "http://models.invalid/corner.bin".to_string(),
+            sha256: sha256_hex(&bytes),
+            patterns: vec![vec![0]],
+            stages,
+        };
+        (bytes, entry)
+    }
+
+    #[test]
+    fn registry_cache() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let dir = std::env::temp_dir().join(format!("solve2048-registry-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (bytes, entry) = registry_model(2);
+        let manifest = Manifest {
+            models: vec![entry.clone()],
+        };
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let served = bytes.clone();
+        let counter = fetches.clone();
+        let registry = Registry::new(manifest.clone(), &dir, move |url: &str| {
+            assert_eq!(url, "http://models.invalid/corner.bin");
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(served.clone())
+        });
+
+        // a miss fetches, a hit doesn't
+        let nets = registry.load("corner").unwrap();
+        assert_eq!(nets.len(), 2);
+        assert_eq!(nets[1].features()[0].weights()[0], 1.0);
+        assert_eq!(registry.bytes("corner").unwrap(), bytes);
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+        // offline works from the cache alone
+        let offline = Registry::offline(manifest.clone(), &dir);
+        assert_eq!(offline.load("corner").unwrap().len(), 2);
+        assert!(offline.load("missing").is_err());
+
+        // a corrupted cache file is fetched again
+        let path = registry.cache_path(&entry);
+        std::fs::write(&path, b"junk").unwrap();
+        assert!(offline.bytes("corner").is_err());
+        assert_eq!(registry.bytes("corner").unwrap(), bytes);
+        assert_eq!(fetches.load(Ordering::SeqCst), 2);
+
+        // a download that fails the checksum isn't cached
+        std::fs::remove_file(&path).unwrap();
+        let tampered = Registry::new(manifest, &dir, |_: &str| Ok(b"tampered".to_vec()));
+        let err = tampered.bytes("corner").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(!path.exists());
+
+        // the patterns have to match the manifest
+        let (_, mut wrong) = registry_model(2);
+        wrong.patterns = vec![vec![1]];
+        let served = bytes.clone();
+        let registry = Registry::new(
+            Manifest {
+                models: vec![wrong],
+            },
+            &dir,
+            move |_: &str| Ok(served.clone()),
+        );
+        assert!(registry.load("corner").is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn registry_rejects_unsafe_names() {
+        let (_, entry) = registry_model(1);
+        let json = |entry: &ModelEntry| {
+            serde_json::to_string(&Manifest {
+                models: vec![entry.clone()],
+            })
+            .unwrap()
+        };
+        assert_eq!(
+            Manifest::from_json(&json(&entry)).unwrap().models,
+            [entry.clone()]
+        );
+        for name in ["../../.bashrc", "/etc/x", "a/b", "a\\b", ".."] {
+            let bad = ModelEntry {
+                name: name.to_string(),
+                ..entry.clone()
+            };
+            assert!(Manifest::from_json(&json(&bad)).is_err(), "{name}");
+            let registry = Registry::offline(Manifest { models: vec![bad] }, "unused");
+            assert!(registry.bytes(name).is_err());
+        }
+        let bad = ModelEntry {
+            sha256: "../x".to_string(),
+            ..entry
+        };
+        assert!(Manifest::from_json(&json(&bad)).is_err());
+    }
+
+    #[test]
+    fn web_state() {
+        let json = include_str!("../tests/fixtures/web_state.json");
+        let state: WebState = serde_json::from_str(json).unwrap();
+        let b: Board = state.board().unwrap();
+        assert_eq!(
+            b,
+            Board::from_arr(&[1, 2, 3, 4, 0, 1, 0, 2, 0, 0, 0, 1, 0, 0, 0, 0])
+        );
+        assert_eq!(state.score, 60);
+        assert_eq!(WebState::from_board(&b, 60), state);
+        assert_eq!(serde_json::to_string(&state).unwrap(), json.trim_end());
+
+        // other sizes go through GameBoard
+        let b3 = Board3::new();
+        let state = WebState::from_board(&b3, 0);
+        assert!(state.over);
+        assert_eq!(state.board::<Board3>(), Ok(b3));
+        assert_eq!(
+            state.board::<Board>(),
+            Err(WebStateError::Size {
+                expected: 4,
+                got: 3
+            })
+        );
+
+        let mut won = Board::new();
+        won.set(5, 11);
+        let mut state = WebState::from_board(&won, 20000);
+        assert!(state.won && state.keep_playing && !state.over);
+        state.grid.cells[1][1].as_mut().unwrap().value = 3;
+        assert_eq!(
+            state.board::<Board>(),
+            Err(WebStateError::Tile {
+                x: 1,
+                y: 1,
+                value: 3
+            })
+        );
+        state.grid.cells[1][1].as_mut().unwrap().position.x = 2;
+        assert_eq!(
+            state.board::<Board>(),
+            Err(WebStateError::Position { x: 1, y: 1 })
+        );
+    }
+
+    #[test]
+    fn engine_protocol() {
+        let weights =
+            std::env::temp_dir().join(format!("solve2048-engine-{}.bin", std::process::id()));
+        let net = NTuple::new(vec![Feature::from_weights(
+            &[0],
+            (0..16).map(|i| i as f32).collect(),
+        )]);
+        let mut bytes = Vec::new();
+        net.export(&mut bytes, &WeightFormat::native()).unwrap();
+        std::fs::write(&weights, bytes).unwrap();
+
+        let script = format!(
+            "uei\n\
+             isready\n\
+             go\n\
+             position 2 2 0 0 / 0 0 0 0 / 0 0 0 0 / 0 0 0 4\n\
+             setoption player expectimax depth 1 heuristic.empty_weight 300\n\
+             go\n\
+             setoption player monte_carlo rollouts 10 metric max_tile\n\
+             go movetime 50\n\
+             go rollouts 5\n\
+             setoption player random\n\
+             setoption player ntuple weights {}\n\
+             go\n\
+             position exp: 1 2 1 2 / 2 1 2 1 / 1 2 1 2 / 2 1 2 1\n\
+             go\n\
+             quit\n\
+             isready\n",
+            weights.display()
+        );
+        let mut out = Vec::new();
+        Engine::default().run(script.as_bytes(), &mut out).unwrap();
+        std::fs::remove_file(&weights).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[0].starts_with("id name solve2048"));
+        assert_eq!(lines[1], "option player expectimax monte_carlo ntuple");
+        assert_eq!(lines[2..4], ["ueiok", "readyok"]);
+        assert_eq!(lines[4], "info string error: no position");
+
+        // each search reports the legal moves, its time and its move
+        let searches: Vec<&[&str]> = lines[5..]
+            .split_inclusive(|l| l.starts_with("bestmove") || l.starts_with("info string"))
+            .collect();
+        assert_eq!(searches.len(), 6);
+        for search in [searches[0], searches[1], searches[2], searches[4]] {
+            let scores: Vec<(&str, f32)> = search[..search.len() - 2]
+                .iter()
+                .map(|l| {
+                    let words: Vec<&str> = l.split(' ').collect();
+                    assert_eq!(words[..2], ["info", "move"]);
+                    (words[2], words[4].parse().unwrap())
+                })
+                .collect();
+            let moves: Vec<&str> = scores.iter().map(|&(m, _)| m).collect();
+            assert_eq!(moves, ["up", "down", "left", "right"]);
+            let best = search[search.len() - 1].strip_prefix("bestmove ").unwrap();
+            let top = scores.iter().map(|&(_, s)| s).fold(f32::MIN, f32::max);
+            assert!(scores.contains(&(best, top)));
+            assert!(search[search.len() - 2].starts_with("info time "));
+        }
+        assert_eq!(
+            searches[3],
+            ["info string error: no player named \"random\""]
+        );
+        assert_eq!(searches[5][1], "bestmove none");
+        assert_eq!(lines.last(), Some(&"bestmove none"));
+    }
+
+    #[test]
+    fn ntuple_learn_episode_seeded() {
+        let learn = |seed| {
+            let mut net = NTuple::new(vec![
+                Feature::new(&[0, 1, 2, 3]),
+                Feature::new(&[0, 4, 8, 12]),
+            ]);
+            let mut env: Env = Env::new();
+            net.learn_episode(&mut env, seed, 0.1);
+            (net.features()[0].weights().to_vec(), env.score())
+        };
+        assert_eq!(learn(Some(5)), learn(Some(5)));
+        assert_ne!(learn(Some(5)).0, vec![0.0; 1 << 16]);
+    }
+
+    #[test]
+    fn gym_env() {
+        let mut env: Env = Env::new();
+        let obs = env.reset(Some(9));
+        assert_eq!(obs.len(), 16);
+        assert_eq!(obs.iter().filter(|&&e| e > 0.0).count(), 2);
+        assert_eq!(Env::<Board>::new().reset(Some(9)), obs);
+        let mut three: Env = Env::new().with_rules(SpawnRules::CLASSIC.with_initial(3));
+        let obs3 = three.reset(Some(9));
+        assert_eq!(obs3.iter().filter(|&&e| e > 0.0).count(), 3);
+
+        // a move that changes nothing is a no-op
+        let b = *env.board();
+        if let Some(m) = Move::all()
+            .into_iter()
+            .find(|&m| !b.legal_moves().contains(m))
+        {
+            let step = env.step(m);
+            assert!(!step.info.legal);
+            assert_eq!((step.reward, *env.board(), env.moves()), (0.0, b, 0));
+        }
+
+        let mut total = 0.0;
+        let done = loop {
+            let mask = env.legal_mask();
+            for m in Move::all() {
+                assert_eq!(
+                    mask[m.to_int() as usize],
+                    env.board().legal_moves().contains(m)
+                );
+            }
+            let m = Move::all()
+                .into_iter()
+                .find(|m| mask[m.to_int() as usize])
+                .unwrap();
+            let step = env.step(m);
+            total += step.reward;
+            assert_eq!(step.obs, env.observation());
+            if step.done {
+                break step;
+            }
+        };
+        assert_eq!(done.info.score as f32, total);
+        assert_eq!(env.legal_mask(), [false; 4]);
+
+        let mut one_hot = vec![0.0; Encoding::OneHot.size::<Board>()];
+        Encoding::OneHot.encode(env.board(), &mut one_hot);
+        assert_eq!(one_hot.len(), 256);
+        assert_eq!(one_hot.iter().sum::<f32>(), 16.0);
+        let top = env.board().log_max_tile() as usize;
+        assert!(one_hot.chunks(16).any(|cell| cell[top] == 1.0));
+
+        // vectorised games start again as soon as they end
+        let mut envs: VecEnv = VecEnv::new(3, Encoding::OneHot);
+        let obs = envs.reset(Some(9));
+        assert_eq!(obs.len(), 3 * 256);
+        assert_eq!(
+            obs[..256],
+            Env::<Board>::new()
+                .with_encoding(Encoding::OneHot)
+                .reset(Some(9))
+        );
+        let mut finished = 0;
+        while finished < 3 {
+            let moves: Vec<Move> = envs
+                .legal_masks()
+                .iter()
+                .map(|mask| {
+                    Move::all()
+                        .into_iter()
+                        .find(|m| mask[m.to_int() as usize])
+                        .unwrap()
+                })
+                .collect();
+            let step = envs.step(&moves);
+            for (i, &done) in step.dones.iter().enumerate() {
+                if done {
+                    finished += 1;
+                    assert!(step.infos[i].afterstate.max_tile() > 2);
+                    assert_eq!(envs.envs()[i].moves(), 0);
+                }
+            }
+        }
+    }
 }
 
 use std::time::{Duration, Instant};
 
+/// Play one game, returning the score and the largest tile. The game is
+/// refereed on a `WideBoard`, so it carries on correctly past 32768; players
+/// see those tiles as 32768, and when that leaves them no move the first
+/// legal one is made for them.
 pub fn play_game<P: Player>(player: &P, max_moves: u32, show_moves: bool) -> (u32, u32) {
-    let mut b = Board::new();
-    let mut rng = Rng::new();
-    b.add_random_tile(&mut rng);
-    b.add_random_tile(&mut rng);
+    play_variant(player, &SpawnRules::CLASSIC, max_moves, show_moves)
+}
+
+/// `play_game` with tiles spawning according to `rules`.
+pub fn play_variant<P: Player>(
+    player: &P,
+    rules: &SpawnRules,
+    max_moves: u32,
+    show_moves: bool,
+) -> (u32, u32) {
+    let mut spawner = RandomSpawner::new(*rules, Rng::new());
+    play_against(player, &mut spawner, max_moves, show_moves)
+}
+
+/// `play_game` with `spawner` placing the tiles, e.g. an `AdversarialSpawner`.
+pub fn play_against<P: Player, S: Spawner<WideBoard>>(
+    player: &P,
+    spawner: &mut S,
+    max_moves: u32,
+    show_moves: bool,
+) -> (u32, u32) {
+    referee(
+        |b: &WideBoard| player.next_move_with_eval(&b.to_board_saturating()),
+        spawner,
+        max_moves,
+        show_moves,
+        None,
+    )
+}
+
+/// Play one game like `play_variant`, with spawns drawn from `seed`, and
+/// return its record, including the player's evaluations where it gives them.
+pub fn record_game<P: Player>(
+    player: &P,
+    rules: &SpawnRules,
+    seed: u64,
+    max_moves: u32,
+) -> GameRecord {
+    let mut record = GameRecord::new(*rules, Some(seed));
+    let mut spawner = RandomSpawner::new(*rules, Rng::with_seed(seed));
+    referee(
+        |b: &WideBoard| player.next_move_with_eval(&b.to_board_saturating()),
+        &mut spawner,
+        max_moves,
+        false,
+        Some(&mut record),
+    );
+    record
+}
+
+/// Play one game on a board of type `B`, e.g. `Board3` or `Board6`.
+pub fn play_game_on<B: GameBoard, P: Player<B>>(
+    player: &P,
+    rules: &SpawnRules,
+    max_moves: u32,
+    show_moves: bool,
+) -> (u32, u32) {
+    let mut spawner = RandomSpawner::new(*rules, Rng::new());
+    referee(
+        |b: &B| player.next_move_with_eval(b),
+        &mut spawner,
+        max_moves,
+        show_moves,
+        None,
+    )
+}
+
+/// Play a game out. The evaluations `next_move` returns only end up in
+/// `record`.
+fn referee<B: GameBoard>(
+    next_move: impl Fn(&B) -> Option<(Move, Option<f32>)>,
+    spawner: &mut impl Spawner<B>,
+    max_moves: u32,
+    show_moves: bool,
+    mut record: Option<&mut GameRecord>,
+) -> (u32, u32) {
+    let mut b = B::new();
+    let start = spawn_tiles(&mut b, spawner, spawner.rules().initial);
+    if let Some(record) = record.as_deref_mut() {
+        record.start = start;
+    }
 
     let mut score = 0;
     let mut total_moves = 0;
@@ -218,8 +1353,10 @@ pub fn play_game<P: Player>(player: &P, max_moves: u32, show_moves: bool) -> (u3
     loop {
         let start_time = Instant::now();
 
-        let m = match player.next_move(&b) {
-            Some(mv) => mv,
+        // Players see tiles past 32768 as 32768, which `Board` doesn't merge,
+        // so they can find no move where the real board still has one.
+        let (m, eval) = match next_move(&b).or_else(|| Some((b.afterstates().next()?.0, None))) {
+            Some(choice) => choice,
             None => break,
         };
 
@@ -229,7 +1366,15 @@ pub fn play_game<P: Player>(player: &P, max_moves: u32, show_moves: bool) -> (u3
 
         if let Some(s) = b.make_move(m) {
             score += s;
-            b.add_random_tile(&mut rng);
+            let spawns = spawn_tiles(&mut b, spawner, spawner.rules().per_move);
+            if let Some(record) = record.as_deref_mut() {
+                record.turns.push(Turn {
+                    mv: m,
+                    spawns,
+                    eval,
+                });
+                record.score = score;
+            }
         }
 
         if show_moves {
@@ -247,6 +1392,21 @@ pub fn play_game<P: Player>(player: &P, max_moves: u32, show_moves: bool) -> (u3
     (score, max_tile)
 }
 
+/// Let `spawner` place up to `count` tiles on `b`, returning where they went.
+fn spawn_tiles<B: GameBoard>(b: &mut B, spawner: &mut impl Spawner<B>, count: u8) -> Vec<Spawn> {
+    let mut spawns = Vec::new();
+    for _ in 0..count {
+        if b.num_empty() == 0 {
+            break;
+        }
+        let (cell, exponent) = spawner.place(b);
+        b.set(cell, exponent);
+        spawns.push(Spawn { cell, exponent });
+    }
+    spawns
+}
+
+#[cfg(feature = "cli")]
 pub fn play_monte_carlo(niter: u32, ngames: u32, metric: MonteCarloMetric) {
     let player = MonteCarloPlayer::new(niter, metric);
 
@@ -256,11 +1416,11 @@ pub fn play_monte_carlo(niter: u32, ngames: u32, metric: MonteCarloMetric) {
     }
 }
 
+#[cfg(feature = "training")]
 pub fn tdl_learn(save_path: &str, alpha: f32, ngames: u32) {
     let mut net = NTuple::default();
 
-    let mut rng = Rng::new();
-    let mut path = Vec::with_capacity(20000);
+    let mut env: Env = Env::new();
 
     let print_stats_every = 1000;
 
@@ -271,28 +1431,10 @@ pub fn tdl_learn(save_path: &str, alpha: f32, ngames: u32) {
 
     let threads = 10;
     for n in (1..=ngames).step_by(threads) {
-        let mut b = Board::new();
-        b.add_random_tile(&mut rng);
-        b.add_random_tile(&mut rng);
-        let mut score = 0;
-        let mut moves = 0;
-
-        loop {
-            let mv = net.next_move(&b);
-            if mv.is_none() {
-                break;
-            }
-            let mv = mv.unwrap();
-            moves += 1;
-
-            let rec = b.make_move_and_record(mv).unwrap();
-            b.add_random_tile(&mut rng);
-            score += rec.score;
-            path.push(rec);
-        }
-
-        net.backward(&mut path, alpha);
-        path.clear();
+        net.learn_episode(&mut env, None, alpha);
+        let b = env.board();
+        let score = env.score();
+        let moves = env.moves();
 
         if n % print_stats_every == 0 {
             let max_tile_avg = max_tile_total as f32 / print_stats_every as f32;
@@ -317,6 +1459,7 @@ pub fn tdl_learn(save_path: &str, alpha: f32, ngames: u32) {
     net.save_weights(&mut writer);
 }
 
+#[cfg(feature = "training")]
 fn print_tile_freq(tile_counter: &[u32; 16]) {
     let tile_sum: u32 = tile_counter.iter().sum();
     let mut cumulative = [0; 16];
@@ -340,69 +1483,3 @@ fn print_tile_freq(tile_counter: &[u32; 16]) {
     }
     println!("========================\n");
 }
-
-#[wasm_bindgen]
-extern "C" {
-    fn alert(s: &str);
-}
-
-#[wasm_bindgen]
-pub fn monte_carlo(arr: &[i32]) -> i32 {
-    let b = Board::from_arr(arr);
-    let next_move = MonteCarloPlayer::default().next_move(&b);
-    match next_move {
-        Some(m) => m.to_int(),
-        None => -1,
-    }
-}
-
-#[wasm_bindgen]
-pub fn expectimax(arr: &[i32]) -> i32 {
-    let b = Board::from_arr(arr);
-    let next_move = ExpectimaxPlayer::default().next_move(&b);
-    match next_move {
-        Some(m) => m.to_int(),
-        None => -1,
-    }
-}
-
-#[wasm_bindgen]
-pub fn build_ntuple(weights: &[u8]) -> NTuple {
-    console_error_panic_hook::set_once();
-    let mut net = NTuple::default();
-    net.load_weights(&mut weights.as_ref());
-    net
-}
-
-#[wasm_bindgen]
-pub fn ntuple(net: &NTuple, arr: &[i32]) -> i32 {
-    // statically load the network weights
-    let b = Board::from_arr(arr);
-    let next_move = net.next_move(&b);
-    match next_move {
-        Some(m) => m.to_int(),
-        None => -1,
-    }
-}
-
-#[wasm_bindgen]
-pub fn random_available_move(arr: &[i32]) -> i32 {
-    let b = Board::from_arr(arr);
-    let mut available = [Move::Up; 4];
-    let mut available_len = 0;
-
-    for m in Move::all() {
-        let res = b.clone().make_move(m);
-        if res.is_some() {
-            available[available_len] = m;
-            available_len += 1;
-        }
-    }
-    if available_len == 0 {
-        return -1;
-    }
-
-    let mut rng = Rng::new();
-    let i = rng.u8(0..available_len as u8);
-    return available[i as usize].to_int();
-}
@@ -1,8 +1,11 @@
-use crate::{Board, Move, Player};
+use crate::par::*;
+use crate::{GameBoard, Move, Player, SpawnRules};
 use fastrand::Rng;
-// use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+/// What a rollout scores. Serialised in snake case, e.g. `"max_tile"`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum MonteCarloMetric {
     Sum,
     MaxTile,
@@ -14,15 +17,12 @@ pub enum MonteCarloMetric {
 pub struct MonteCarloPlayer {
     niter: u32,
     metric: MonteCarloMetric,
+    rules: SpawnRules,
 }
 
-impl Player for MonteCarloPlayer {
-    fn next_move(&self, b: &Board) -> Option<Move> {
-        let res = Move::all()
-            .iter()
-            .map(|&m| (m, self.explore_move(b, m)))
-            .filter(|&(_, s)| s > 0)
-            .max_by_key(|&(_, s)| s)?;
+impl<B: GameBoard> Player<B> for MonteCarloPlayer {
+    fn next_move(&self, b: &B) -> Option<Move> {
+        let res = self.rollout_totals(b).into_iter().max_by_key(|&(_, s)| s)?;
 
         let (mv, _) = res;
         Some(mv)
@@ -31,34 +31,68 @@ impl Player for MonteCarloPlayer {
 
 impl MonteCarloPlayer {
     pub fn new(niter: u32, metric: MonteCarloMetric) -> Self {
-        Self { niter, metric }
+        Self {
+            niter,
+            metric,
+            rules: SpawnRules::CLASSIC,
+        }
     }
 
     pub fn default() -> Self {
-        Self {
-            niter: 200,
-            metric: MonteCarloMetric::Sum,
+        Self::new(200, MonteCarloMetric::Sum)
+    }
+
+    /// Roll out games under `rules` instead of the classic spawns.
+    pub fn with_rules(mut self, rules: SpawnRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// The average rollout result after each legal move of `b`, merge score
+    /// included, in `Move::all` order.
+    pub fn move_scores<B: GameBoard>(&self, b: &B) -> Vec<(Move, f32)> {
+        self.rollout_totals(b)
+            .into_iter()
+            .map(|(m, total)| (m, self.average(total)))
+            .collect()
+    }
+
+    /// A total from `explore_afterstate` per rollout.
+    pub(crate) fn average(&self, total: u32) -> f32 {
+        total as f32 / self.niter.max(1) as f32
+    }
+
+    /// `explore_afterstate` for each legal move of `b`.
+    pub(crate) fn rollout_totals<B: GameBoard>(&self, b: &B) -> Vec<(Move, u32)> {
+        b.afterstates()
+            .map(|(m, after, reward)| (m, self.explore_afterstate(&after, reward)))
+            .collect()
+    }
+
+    pub fn explore_move<B: GameBoard>(&self, b: &B, m: Move) -> u32 {
+        let mut b: B = *b;
+        match b.make_move(m) {
+            Some(s) => self.explore_afterstate(&b, s),
+            None => 0,
         }
     }
 
-    pub fn explore_move(&self, b: &Board, m: Move) -> u32 {
-        let mut b: Board = *b;
-        let mut score: u32 = match b.make_move(m) {
-            Some(s) => s,
-            None => return 0,
-        };
+    /// Total of `niter` rollouts from `after`, reached with a merge score of
+    /// `score`.
+    fn explore_afterstate<B: GameBoard>(&self, after: &B, mut score: u32) -> u32 {
+        let mut b: B = *after;
         let mut rng = Rng::new();
-        b.add_random_tile(&mut rng);
+        b.spawn(&self.rules, &mut rng);
 
         score += (0..self.niter)
-            // .into_par_iter()
+            .into_par_iter()
             .map(|_| self.random_run(&b))
             .sum::<u32>();
 
         score
     }
-    pub fn random_run(&self, b: &Board) -> u32 {
-        let mut b: Board = *b;
+    pub fn random_run<B: GameBoard>(&self, b: &B) -> u32 {
+        let mut b: B = *b;
         let mut nmoves = 0;
         let mut score = 0;
         let mut fails = 0;
@@ -68,7 +102,7 @@ impl MonteCarloPlayer {
             if let Some(delta) = b.make_move(Move::rand(&mut rng)) {
                 score += delta;
                 nmoves += 1;
-                b.add_random_tile(&mut rng);
+                b.spawn(&self.rules, &mut rng);
                 fails = 0;
             } else {
                 fails += 1;
@@ -0,0 +1,183 @@
+//! Text notation for boards and moves, for test fixtures, command lines and
+//! logs.
+//!
+//! A board can be written three ways:
+//!
+//! - as tile values, the way `Display` prints it: `"2 0 0 4 / 0 0 0 0 / ..."`.
+//!   Rows are separated by `/` or newlines, cells by spaces or commas, and
+//!   `.` stands for an empty cell;
+//! - as exponents, the same grid prefixed with `exp:`: `"exp: 1 0 0 2 / ..."`;
+//! - as 16 hex digits, one exponent per cell in reading order, the way `{:x}`
+//!   prints it: `"1002000000000000"`. With a `0x` prefix the digits are
+//!   `Board::raw` instead, which runs the other way round.
+//!
+//! Serde writes boards as hex digits and moves as their names, and reads
+//! anything `from_str` does.
+use crate::{Board, Move};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Why a board or move failed to parse.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    msg: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(msg: String) -> Self {
+        Self { msg }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.msg)
+    }
+}
+
+impl Error for ParseError {}
+
+impl FromStr for Board {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let s = s.trim();
+        if let Some(raw) = s.strip_prefix("0x") {
+            return u64::from_str_radix(raw, 16)
+                .map(Board::from_raw)
+                .map_err(|e| ParseError::new(format!("bad raw board {s:?}: {e}")));
+        }
+        if s.len() == 16 && s.chars().all(|c| c.is_ascii_hexdigit()) {
+            let mut b = Board::new();
+            for (i, c) in s.chars().enumerate() {
+                b.set(i as u8, c.to_digit(16).unwrap() as u8);
+            }
+            return Ok(b);
+        }
+        match s.strip_prefix("exp:") {
+            Some(grid) => parse_grid(grid, parse_exponent),
+            None => parse_grid(s, parse_tile),
+        }
+    }
+}
+
+/// Read 16 cells laid out as 4 rows of 4.
+fn parse_grid(s: &str, cell: impl Fn(&str) -> Result<u8, ParseError>) -> Result<Board, ParseError> {
+    let rows: Vec<&str> = s
+        .split(['/', '\n'])
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .collect();
+    if rows.len() != 4 {
+        return Err(ParseError::new(format!(
+            "expected 4 rows, found {}",
+            rows.len()
+        )));
+    }
+    let mut b = Board::new();
+    for (r, row) in rows.iter().enumerate() {
+        let cells: Vec<&str> = row
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|c| !c.is_empty())
+            .collect();
+        if cells.len() != 4 {
+            return Err(ParseError::new(format!("row {row:?} doesn't have 4 cells")));
+        }
+        for (c, text) in cells.iter().enumerate() {
+            b.set((4 * r + c) as u8, cell(text)?);
+        }
+    }
+    Ok(b)
+}
+
+fn parse_tile(s: &str) -> Result<u8, ParseError> {
+    if s == "." {
+        return Ok(0);
+    }
+    match s.parse::<u32>() {
+        Ok(0) => Ok(0),
+        Ok(v)
+            if v >= 2
+                && v.is_power_of_two()
+                && v.trailing_zeros() <= Board::MAX_EXPONENT as u32 =>
+        {
+            Ok(v.trailing_zeros() as u8)
+        }
+        _ => Err(ParseError::new(format!("{s:?} is not a tile"))),
+    }
+}
+
+fn parse_exponent(s: &str) -> Result<u8, ParseError> {
+    if s == "." {
+        return Ok(0);
+    }
+    match s.parse::<u8>() {
+        Ok(e) if e <= Board::MAX_EXPONENT => Ok(e),
+        _ => Err(ParseError::new(format!("{s:?} is not an exponent"))),
+    }
+}
+
+/// The 16 cell exponents in reading order.
+impl fmt::LowerHex for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for i in 0..16 {
+            write!(f, "{:x}", self.at(i))?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{self:x}"))
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Move::Up => "up",
+            Move::Down => "down",
+            Move::Left => "left",
+            Move::Right => "right",
+        })
+    }
+}
+
+/// Accepts names (`"up"`), initials (`"U"`) and arrows (`"↑"`), in any case.
+impl FromStr for Move {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "up" | "u" | "↑" => Ok(Move::Up),
+            "down" | "d" | "↓" => Ok(Move::Down),
+            "left" | "l" | "←" => Ok(Move::Left),
+            "right" | "r" | "→" => Ok(Move::Right),
+            _ => Err(ParseError::new(format!("{s:?} is not a move"))),
+        }
+    }
+}
+
+impl Serialize for Move {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Move {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
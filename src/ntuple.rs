@@ -1,6 +1,7 @@
-use crate::{Board, Move, Player};
+use crate::{Board, Env, Evaluator, Move, Player};
 use std::io::{Read, Write};
 use std::mem::size_of;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 pub struct Feature {
@@ -22,6 +23,22 @@ impl Feature {
         Feature { weights, iso }
     }
 
+    /// Create a feature from a pattern and an already populated weight table
+    pub fn from_weights(pattern: &[u8], weights: Vec<f32>) -> Self {
+        assert_eq!(weights.len(), 1 << (pattern.len() * 4));
+        let iso: [Vec<u8>; 8] = Self::isometries(pattern);
+        Feature { weights, iso }
+    }
+
+    /// Board cells covered by the feature, in index order
+    pub fn pattern(&self) -> &[u8] {
+        &self.iso[0]
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
     pub fn with_weights(pattern: &[u8], bytes: &mut impl Read) -> Self {
         let mut feat = Self::new(pattern);
         feat.load_weights(bytes);
@@ -128,7 +145,7 @@ impl Feature {
     }
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct NTuple {
     feats: Vec<Feature>,
 }
@@ -152,6 +169,22 @@ impl Player for NTuple {
     fn next_move(&self, b: &Board) -> Option<Move> {
         return self.select_best_move(b);
     }
+
+    /// The value is the chosen move's reward plus its afterstate estimate.
+    fn next_move_with_eval(&self, b: &Board) -> Option<(Move, Option<f32>)> {
+        let (mv, v) = self.best_afterstate(b)?;
+        Some((mv, Some(v)))
+    }
+}
+
+impl Evaluator for NTuple {
+    fn evaluate(&self, b: &Board) -> f32 {
+        self.estimate(b)
+    }
+
+    fn counts_score(&self) -> bool {
+        true
+    }
 }
 
 impl NTuple {
@@ -182,6 +215,10 @@ impl NTuple {
         NTuple { feats }
     }
 
+    pub fn features(&self) -> &[Feature] {
+        &self.feats
+    }
+
     pub fn load_weights(&mut self, bytes: &mut impl Read) {
         let mut buf = [0; size_of::<u64>()];
         bytes.read(&mut buf).unwrap();
@@ -209,26 +246,35 @@ impl NTuple {
     }
 
     fn select_best_move(&self, b: &Board) -> Option<Move> {
-        let moves = Move::all();
-        let mut best_move = Move::Up;
-        // minimum f32 value
-        let mut best_score = f32::MIN;
-
-        for mv in moves {
-            let mut b_copy = *b;
-            if let Some(score) = b_copy.make_move(mv) {
-                let score = score as f32 + self.estimate(&b_copy);
-                if score > best_score {
-                    best_score = score;
-                    best_move = mv;
-                }
+        self.best_afterstate(b).map(|(mv, _)| mv)
+    }
+
+    /// The move with the highest reward plus afterstate estimate, and that value.
+    fn best_afterstate(&self, b: &Board) -> Option<(Move, f32)> {
+        let mut best = None;
+        for (mv, after, reward) in b.afterstates() {
+            let score = reward as f32 + self.estimate(&after);
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((mv, score));
             }
         }
+        best
+    }
 
-        if best_score == f32::MIN {
-            return None;
+    /// Play a new game in `env`, reset with `seed`, then learn from its
+    /// afterstates by `backward`.
+    pub fn learn_episode(&mut self, env: &mut Env, seed: Option<u64>, alpha: f32) {
+        env.reset(seed);
+        let mut path = Vec::new();
+        while let Some(m) = self.next_move(env.board()) {
+            let step = env.step(m);
+            path.push(MoveRecord {
+                m,
+                board_after: step.info.afterstate,
+                score: step.reward as u32,
+            });
         }
-        return Some(best_move);
+        self.backward(&mut path, alpha);
     }
 
     pub fn backward(&mut self, path: &mut Vec<MoveRecord>, alpha: f32) {
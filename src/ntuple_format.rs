@@ -0,0 +1,310 @@
+//! Import and export of n-tuple weights in the layouts used by other 2048
+//! TD-learning codebases.
+//!
+//! Foreign networks differ from ours in three ways: the container around the
+//! weight tables, the numbering of the board cells that patterns refer to, and
+//! which pattern cell ends up in the lowest nibble of a tuple index. The tables
+//! themselves are always `16^n` little-endian `f32`s, so converting never has
+//! to touch a weight, only the patterns.
+use crate::{Feature, NTuple};
+use std::io::{self, Read, Write};
+use std::mem::size_of;
+
+/// Largest tuple we are willing to allocate a table for (16^8 weights = 16 GiB).
+const MAX_TUPLE_LEN: usize = 7;
+
+/// How a foreign codebase numbers the 16 board cells in its patterns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CellOrder {
+    to_native: [u8; 16],
+}
+
+impl CellOrder {
+    /// Row-major from the top-left, as `Board` and TDL2048 number cells.
+    pub fn row_major() -> Self {
+        Self::from_fn(|i| i)
+    }
+
+    /// Column-major from the top-left.
+    pub fn column_major() -> Self {
+        Self::from_fn(|i| (i % 4) * 4 + i / 4)
+    }
+
+    /// Row-major with each row numbered right to left, as in codebases that
+    /// keep the leftmost column in the highest nibble of a row.
+    pub fn mirrored() -> Self {
+        Self::from_fn(|i| (i / 4) * 4 + 3 - i % 4)
+    }
+
+    /// Build an order from a map of foreign cell index to `Board` cell index.
+    /// Returns `None` if `to_native` is not a permutation of `0..16`.
+    pub fn from_map(to_native: [u8; 16]) -> Option<Self> {
+        let mut seen = [false; 16];
+        for &c in &to_native {
+            if c >= 16 || seen[c as usize] {
+                return None;
+            }
+            seen[c as usize] = true;
+        }
+        Some(Self { to_native })
+    }
+
+    fn from_fn(f: impl Fn(u8) -> u8) -> Self {
+        let mut to_native = [0; 16];
+        for i in 0..16 {
+            to_native[i as usize] = f(i);
+        }
+        Self { to_native }
+    }
+
+    pub fn to_native(&self, cell: u8) -> u8 {
+        self.to_native[cell as usize]
+    }
+
+    pub fn from_native(&self, cell: u8) -> u8 {
+        self.to_native.iter().position(|&c| c == cell).unwrap() as u8
+    }
+}
+
+/// Which pattern cell occupies the lowest nibble of a tuple index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexOrder {
+    /// The first cell of the pattern is the lowest nibble (this crate, TDL2048).
+    LowFirst,
+    /// The first cell of the pattern is the highest nibble.
+    HighFirst,
+}
+
+/// The framing around the weight tables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Container {
+    /// The TDL2048 layout: a `size_t` feature count, then for each feature an
+    /// `i32` name length, a name such as `"6-tuple pattern 012345"`, a
+    /// `size_t` weight count and the weights. `size_width` is the byte width
+    /// of `size_t` on the machine that wrote the file.
+    Tdl2048 { size_width: usize },
+    /// Bare weight tables back to back. The file says nothing about its own
+    /// patterns, so they must be supplied.
+    Raw { patterns: Vec<Vec<u8>> },
+}
+
+/// A complete description of a foreign weight file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WeightFormat {
+    pub container: Container,
+    pub cells: CellOrder,
+    pub index: IndexOrder,
+}
+
+impl WeightFormat {
+    /// The layout written by `NTuple::save_weights` on 64-bit hosts, which is
+    /// byte-for-byte the layout of TDL2048 compiled for x86-64.
+    pub fn native() -> Self {
+        Self::tdl2048(size_of::<u64>())
+    }
+
+    pub fn tdl2048(size_width: usize) -> Self {
+        assert!(size_width == 4 || size_width == 8);
+        Self {
+            container: Container::Tdl2048 { size_width },
+            cells: CellOrder::row_major(),
+            index: IndexOrder::LowFirst,
+        }
+    }
+
+    pub fn raw(patterns: &[&[u8]]) -> Self {
+        Self {
+            container: Container::Raw {
+                patterns: patterns.iter().map(|p| p.to_vec()).collect(),
+            },
+            cells: CellOrder::row_major(),
+            index: IndexOrder::LowFirst,
+        }
+    }
+
+    pub fn with_cells(mut self, cells: CellOrder) -> Self {
+        self.cells = cells;
+        self
+    }
+
+    pub fn with_index(mut self, index: IndexOrder) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Translate a pattern written in this format into `Board` cells.
+    ///
+    /// Reading the pattern backwards turns a high-first index into a
+    /// low-first one, so the weight table can be used unchanged.
+    fn pattern_to_native(&self, pattern: &[u8]) -> io::Result<Vec<u8>> {
+        validate_pattern(pattern)?;
+        let mut native: Vec<u8> = pattern.iter().map(|&c| self.cells.to_native(c)).collect();
+        if self.index == IndexOrder::HighFirst {
+            native.reverse();
+        }
+        Ok(native)
+    }
+
+    fn pattern_from_native(&self, pattern: &[u8]) -> Vec<u8> {
+        let mut foreign: Vec<u8> = pattern.iter().map(|&c| self.cells.from_native(c)).collect();
+        if self.index == IndexOrder::HighFirst {
+            foreign.reverse();
+        }
+        foreign
+    }
+}
+
+impl NTuple {
+    /// Read a network written in `format`.
+    pub fn import(bytes: &mut impl Read, format: &WeightFormat) -> io::Result<Self> {
+        let mut feats = Vec::new();
+        match &format.container {
+            Container::Tdl2048 { size_width } => {
+                let count = read_size(bytes, *size_width)?;
+                for _ in 0..count {
+                    let name_len = read_i32(bytes)?;
+                    if !(0..=256).contains(&name_len) {
+                        return Err(invalid(format!("bad feature name length {name_len}")));
+                    }
+                    let mut name = vec![0; name_len as usize];
+                    bytes.read_exact(&mut name)?;
+                    let name = String::from_utf8(name).map_err(|e| invalid(e.to_string()))?;
+                    let pattern = parse_feature_name(&name)?;
+                    let len = read_size(bytes, *size_width)?;
+                    if len != table_len(&pattern) {
+                        return Err(invalid(format!("{name} has {len} weights")));
+                    }
+                    let weights = read_table(bytes, len)?;
+                    feats.push(Feature::from_weights(
+                        &format.pattern_to_native(&pattern)?,
+                        weights,
+                    ));
+                }
+            }
+            Container::Raw { patterns } => {
+                for pattern in patterns {
+                    let native = format.pattern_to_native(pattern)?;
+                    let weights = read_table(bytes, table_len(pattern))?;
+                    feats.push(Feature::from_weights(&native, weights));
+                }
+                if bytes.read(&mut [0])? != 0 {
+                    return Err(invalid("trailing data after last table".to_string()));
+                }
+            }
+        }
+        Ok(NTuple::new(feats))
+    }
+
+    /// Write the network in `format`. For `Container::Raw` the format's patterns must
+    /// match the network's, since they are not stored in the file.
+    pub fn export(&self, out: &mut impl Write, format: &WeightFormat) -> io::Result<()> {
+        match &format.container {
+            Container::Tdl2048 { size_width } => {
+                write_size(out, self.features().len(), *size_width)?;
+                for feat in self.features() {
+                    let name = feature_name(&format.pattern_from_native(feat.pattern()));
+                    out.write_all(&(name.len() as i32).to_le_bytes())?;
+                    out.write_all(name.as_bytes())?;
+                    write_size(out, feat.weights().len(), *size_width)?;
+                    write_table(out, feat.weights())?;
+                }
+            }
+            Container::Raw { patterns } => {
+                let expected: Vec<Vec<u8>> = self
+                    .features()
+                    .iter()
+                    .map(|f| format.pattern_from_native(f.pattern()))
+                    .collect();
+                if *patterns != expected {
+                    return Err(invalid(format!(
+                        "network patterns {expected:?} differ from format patterns {patterns:?}"
+                    )));
+                }
+                for feat in self.features() {
+                    write_table(out, feat.weights())?;
+                }
+            }
+        }
+        out.flush()
+    }
+}
+
+/// TDL2048 names a feature `"<n>-tuple pattern <hex cells>"`.
+fn feature_name(pattern: &[u8]) -> String {
+    let cells: String = pattern.iter().map(|c| format!("{c:x}")).collect();
+    format!("{}-tuple pattern {}", pattern.len(), cells)
+}
+
+fn parse_feature_name(name: &str) -> io::Result<Vec<u8>> {
+    let bad = || invalid(format!("unrecognised feature name {name:?}"));
+    let (len, cells) = name.split_once("-tuple pattern ").ok_or_else(bad)?;
+    let len: usize = len.parse().map_err(|_| bad())?;
+    let pattern = cells
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(bad)?;
+    if pattern.len() != len {
+        return Err(bad());
+    }
+    validate_pattern(&pattern)?;
+    Ok(pattern)
+}
+
+/// A pattern is 1 to `MAX_TUPLE_LEN` distinct cells of the board.
+pub(crate) fn validate_pattern(pattern: &[u8]) -> io::Result<()> {
+    let mut seen = [false; 16];
+    if pattern.is_empty() || pattern.len() > MAX_TUPLE_LEN {
+        return Err(invalid(format!(
+            "unsupported tuple length {}",
+            pattern.len()
+        )));
+    }
+    for &c in pattern {
+        if c >= 16 || seen[c as usize] {
+            return Err(invalid(format!("invalid pattern {pattern:?}")));
+        }
+        seen[c as usize] = true;
+    }
+    Ok(())
+}
+
+fn table_len(pattern: &[u8]) -> usize {
+    1 << (pattern.len() * 4)
+}
+
+fn read_i32(bytes: &mut impl Read) -> io::Result<i32> {
+    let mut buf = [0; size_of::<i32>()];
+    bytes.read_exact(&mut buf)?;
+    Ok(i32::from_le_bytes(buf))
+}
+
+fn read_size(bytes: &mut impl Read, width: usize) -> io::Result<usize> {
+    let mut buf = [0; size_of::<u64>()];
+    bytes.read_exact(&mut buf[..width])?;
+    usize::try_from(u64::from_le_bytes(buf)).map_err(|e| invalid(e.to_string()))
+}
+
+fn write_size(out: &mut impl Write, size: usize, width: usize) -> io::Result<()> {
+    out.write_all(&(size as u64).to_le_bytes()[..width])
+}
+
+fn read_table(bytes: &mut impl Read, len: usize) -> io::Result<Vec<f32>> {
+    let mut buf = vec![0; len * size_of::<f32>()];
+    bytes.read_exact(&mut buf)?;
+    Ok(buf
+        .chunks_exact(size_of::<f32>())
+        .map(|w| f32::from_le_bytes(w.try_into().unwrap()))
+        .collect())
+}
+
+fn write_table(out: &mut impl Write, weights: &[f32]) -> io::Result<()> {
+    for w in weights {
+        out.write_all(&w.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -0,0 +1,26 @@
+//! Rayon's parallel iterators with the `rayon` feature, and plain iterators
+//! under the same names without it, so the searches are written once.
+#[cfg(feature = "rayon")]
+pub(crate) use rayon::prelude::*;
+
+#[cfg(not(feature = "rayon"))]
+pub(crate) trait IntoParallelIterator: IntoIterator + Sized {
+    fn into_par_iter(self) -> Self::IntoIter {
+        self.into_iter()
+    }
+}
+
+#[cfg(not(feature = "rayon"))]
+impl<I: IntoIterator> IntoParallelIterator for I {}
+
+#[cfg(not(feature = "rayon"))]
+pub(crate) trait IntoParallelRefIterator<T> {
+    fn par_iter(&self) -> std::slice::Iter<'_, T>;
+}
+
+#[cfg(not(feature = "rayon"))]
+impl<T> IntoParallelRefIterator<T> for [T] {
+    fn par_iter(&self) -> std::slice::Iter<'_, T> {
+        self.iter()
+    }
+}
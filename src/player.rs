@@ -1,4 +1,10 @@
-use crate::{Board, Move};
-pub trait Player {
-    fn next_move(&self, b: &Board) -> Option<Move>;
+use crate::{Board, GameBoard, Move};
+pub trait Player<B: GameBoard = Board> {
+    fn next_move(&self, b: &B) -> Option<Move>;
+
+    /// `next_move` together with the player's own value for the position,
+    /// if it has one, e.g. for game records.
+    fn next_move_with_eval(&self, b: &B) -> Option<(Move, Option<f32>)> {
+        self.next_move(b).map(|m| (m, None))
+    }
 }
@@ -0,0 +1,468 @@
+//! The `solve2048` Python module: boards, vectorised environments, the
+//! players and n-tuple networks, for notebooks to run games at Rust speed.
+//! Build it with `maturin develop --release`, see `pyproject.toml`.
+//!
+//! Moves are numbered as by `Move::to_int` (`MOVES` names them), and boards
+//! travel in batches as numpy arrays of `Board::raw`. Arrays made here are
+//! handed to numpy without copying.
+use crate::ntuple_format::validate_pattern;
+use crate::par::*;
+use crate::{Board, Encoding, Env, Move, NTuple, PlayerConfig, VecEnv, WeightFormat};
+use numpy::ndarray::Array2;
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyReadonlyArray1};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyBytes, PyDict, PyList, PyTuple};
+use serde_json::{Map, Value};
+use std::fs::{self, File};
+use std::io::BufReader;
+
+fn value_error(e: impl ToString) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+fn action(a: i64) -> PyResult<Move> {
+    match a {
+        0..=3 => Ok(Move::from_int(a as u32)),
+        _ => Err(value_error(format!("{a} isn't a move, see MOVES"))),
+    }
+}
+
+fn rows<T: numpy::Element>(py: Python<'_>, cols: usize, v: Vec<T>) -> Bound<'_, PyArray2<T>> {
+    let n = v.len().checked_div(cols).unwrap_or(0);
+    Array2::from_shape_vec((n, cols), v)
+        .expect("whole rows")
+        .into_pyarray(py)
+}
+
+#[pyclass(name = "Board", module = "solve2048", frozen, eq, hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct PyBoard(Board);
+
+#[pymethods]
+impl PyBoard {
+    /// A board in any notation `Board` parses, or the empty board.
+    #[new]
+    #[pyo3(signature = (notation = None))]
+    fn new(notation: Option<&str>) -> PyResult<Self> {
+        match notation {
+            Some(s) => s.parse().map(Self).map_err(value_error),
+            None => Ok(Self(Board::new())),
+        }
+    }
+
+    /// 16 exponents in reading order, 0 for empty.
+    #[staticmethod]
+    fn from_exponents(cells: Vec<i32>) -> PyResult<Self> {
+        Board::try_from_exponents(&cells)
+            .map(Self)
+            .map_err(value_error)
+    }
+
+    #[staticmethod]
+    fn from_raw(raw: u64) -> Self {
+        Self(Board::from_raw(raw))
+    }
+
+    #[getter]
+    fn raw(&self) -> u64 {
+        self.0.raw
+    }
+
+    /// The exponents in reading order.
+    fn cells<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<u8>> {
+        (0..16)
+            .map(|i| self.0.at(i))
+            .collect::<Vec<_>>()
+            .into_pyarray(py)
+    }
+
+    /// The board after move `m`, before a tile spawns, and the merge score,
+    /// or None if the move changes nothing.
+    fn slide(&self, m: i64) -> PyResult<Option<(Self, u32)>> {
+        let mut b = self.0;
+        Ok(b.make_move(action(m)?).map(|score| (Self(b), score)))
+    }
+
+    /// The board with a tile spawned, drawn from `seed` if given.
+    #[pyo3(signature = (seed = None))]
+    fn spawn(&self, seed: Option<u64>) -> Self {
+        let mut rng = seed.map_or_else(fastrand::Rng::new, fastrand::Rng::with_seed);
+        let mut b = self.0;
+        b.add_random_tile(&mut rng);
+        Self(b)
+    }
+
+    /// The legal moves, indexed by move number.
+    fn legal_mask(&self) -> [bool; 4] {
+        let legal = self.0.legal_moves();
+        [0, 1, 2, 3].map(|i| legal.contains(Move::from_int(i)))
+    }
+
+    fn game_ended(&self) -> bool {
+        self.0.game_ended()
+    }
+
+    fn max_tile(&self) -> u32 {
+        self.0.max_tile()
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Board('{:x}')", self.0)
+    }
+}
+
+type VecStepArrays<'py> = (
+    Bound<'py, PyArray2<f32>>,
+    Bound<'py, PyArray1<f32>>,
+    Bound<'py, PyArray1<bool>>,
+    Bound<'py, PyDict>,
+);
+
+/// `VecEnv` for Python. Observations come as an array of a row per game,
+/// the rest as an entry per game.
+#[pyclass(name = "VecEnv", module = "solve2048")]
+struct PyVecEnv(VecEnv);
+
+#[pymethods]
+impl PyVecEnv {
+    /// `n` games observed as `"exponents"` or `"one_hot"`.
+    #[new]
+    #[pyo3(signature = (n, encoding = "exponents"))]
+    fn new(n: usize, encoding: &str) -> PyResult<Self> {
+        let encoding = match encoding {
+            "exponents" => Encoding::Exponents,
+            "one_hot" => Encoding::OneHot,
+            _ => return Err(value_error(format!("no encoding named {encoding:?}"))),
+        };
+        Ok(Self(VecEnv::new(n, encoding)))
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    #[getter]
+    fn obs_len(&self) -> usize {
+        self.0.obs_len()
+    }
+
+    #[pyo3(signature = (seed = None))]
+    fn reset<'py>(&mut self, py: Python<'py>, seed: Option<u64>) -> Bound<'py, PyArray2<f32>> {
+        let obs = self.0.reset(seed);
+        rows(py, self.0.obs_len(), obs)
+    }
+
+    /// `(obs, rewards, dones, info)`, with the games' scores, move counts,
+    /// whether their moves were legal, and their afterstates in `info`.
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        actions: PyReadonlyArray1<'py, i64>,
+    ) -> PyResult<VecStepArrays<'py>> {
+        let moves = actions
+            .as_slice()?
+            .iter()
+            .map(|&a| action(a))
+            .collect::<PyResult<Vec<_>>>()?;
+        if moves.len() != self.0.len() {
+            return Err(value_error(format!(
+                "{} actions for {} games",
+                moves.len(),
+                self.0.len()
+            )));
+        }
+        let env = &mut self.0;
+        let step = py.detach(|| env.step(&moves));
+        let info = PyDict::new(py);
+        let infos = &step.infos;
+        info.set_item(
+            "score",
+            infos
+                .iter()
+                .map(|i| i.score)
+                .collect::<Vec<_>>()
+                .into_pyarray(py),
+        )?;
+        info.set_item(
+            "moves",
+            infos
+                .iter()
+                .map(|i| i.moves)
+                .collect::<Vec<_>>()
+                .into_pyarray(py),
+        )?;
+        info.set_item(
+            "legal",
+            infos
+                .iter()
+                .map(|i| i.legal)
+                .collect::<Vec<_>>()
+                .into_pyarray(py),
+        )?;
+        info.set_item(
+            "afterstate",
+            infos
+                .iter()
+                .map(|i| i.afterstate.raw)
+                .collect::<Vec<_>>()
+                .into_pyarray(py),
+        )?;
+        Ok((
+            rows(py, self.0.obs_len(), step.obs),
+            step.rewards.into_pyarray(py),
+            step.dones.into_pyarray(py),
+            info,
+        ))
+    }
+
+    /// A row of four per game, indexed by move number.
+    fn legal_masks<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<bool>> {
+        let masks = self.0.legal_masks().into_iter().flatten().collect();
+        rows(py, 4, masks)
+    }
+
+    fn boards<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<u64>> {
+        self.0
+            .envs()
+            .iter()
+            .map(|env| env.board().raw)
+            .collect::<Vec<_>>()
+            .into_pyarray(py)
+    }
+}
+
+#[pyclass(name = "NTuple", module = "solve2048")]
+struct PyNTuple(NTuple);
+
+#[pymethods]
+impl PyNTuple {
+    /// A network of zero weights over `patterns`, lists of cells, or over
+    /// the usual four 6-tuples.
+    #[new]
+    #[pyo3(signature = (patterns = None))]
+    fn new(patterns: Option<Vec<Vec<u8>>>) -> PyResult<Self> {
+        let Some(patterns) = patterns else {
+            return Ok(Self(NTuple::default()));
+        };
+        for p in &patterns {
+            validate_pattern(p).map_err(value_error)?;
+        }
+        let features = patterns.iter().map(|p| crate::Feature::new(p)).collect();
+        Ok(Self(NTuple::new(features)))
+    }
+
+    /// A network from a weight file in the native format.
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        Ok(Self(NTuple::import(&mut file, &WeightFormat::native())?))
+    }
+
+    #[staticmethod]
+    fn from_bytes(mut data: &[u8]) -> PyResult<Self> {
+        Ok(Self(NTuple::import(&mut data, &WeightFormat::native())?))
+    }
+
+    fn save(&self, path: &str) -> PyResult<()> {
+        let mut bytes = Vec::new();
+        self.0.export(&mut bytes, &WeightFormat::native())?;
+        Ok(fs::write(path, bytes)?)
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let mut bytes = Vec::new();
+        self.0.export(&mut bytes, &WeightFormat::native())?;
+        Ok(PyBytes::new(py, &bytes))
+    }
+
+    #[getter]
+    fn patterns(&self) -> Vec<Vec<usize>> {
+        // as lists of numbers, where u8s would become bytes
+        self.0
+            .features()
+            .iter()
+            .map(|f| f.pattern().iter().map(|&c| c as usize).collect())
+            .collect()
+    }
+
+    /// The network's value of each board in `boards`.
+    fn evaluate<'py>(
+        &self,
+        py: Python<'py>,
+        boards: PyReadonlyArray1<'py, u64>,
+    ) -> PyResult<Bound<'py, PyArray1<f32>>> {
+        let boards = boards.as_slice()?;
+        let net = &self.0;
+        let values: Vec<f32> = py.detach(|| {
+            boards
+                .par_iter()
+                .map(|&raw| net.estimate(&Board::from_raw(raw)))
+                .collect()
+        });
+        Ok(values.into_pyarray(py))
+    }
+
+    /// Move each board's value by its delta, spread over the features, and
+    /// return the new values.
+    fn update<'py>(
+        &mut self,
+        py: Python<'py>,
+        boards: PyReadonlyArray1<'py, u64>,
+        deltas: PyReadonlyArray1<'py, f32>,
+    ) -> PyResult<Bound<'py, PyArray1<f32>>> {
+        let (boards, deltas) = (boards.as_slice()?, deltas.as_slice()?);
+        if boards.len() != deltas.len() {
+            return Err(value_error("a delta per board"));
+        }
+        let values: Vec<f32> = boards
+            .iter()
+            .zip(deltas)
+            .map(|(&raw, &delta)| self.0.update(&Board::from_raw(raw), delta))
+            .collect();
+        Ok(values.into_pyarray(py))
+    }
+
+    /// Learn from `games` games of self-play by TD(0), as `tdl_learn` does,
+    /// and return their scores.
+    #[pyo3(signature = (games, alpha, seed = None))]
+    fn train<'py>(
+        &mut self,
+        py: Python<'py>,
+        games: u32,
+        alpha: f32,
+        seed: Option<u64>,
+    ) -> Bound<'py, PyArray1<u32>> {
+        let net = &mut self.0;
+        let scores: Vec<u32> = py.detach(|| {
+            let mut env: Env = Env::new();
+            // later games carry on from the first one's spawns
+            (0..games)
+                .map(|i| {
+                    net.learn_episode(&mut env, seed.filter(|_| i == 0), alpha);
+                    env.score()
+                })
+                .collect()
+        });
+        scores.into_pyarray(py)
+    }
+}
+
+type ChoiceArrays<'py> = (Bound<'py, PyArray1<i32>>, Bound<'py, PyArray2<f32>>);
+
+/// A player by name, as in `PlayerConfig`, with its config as keyword
+/// arguments: `Player("expectimax", depth=2, heuristic={"empty_weight": 300})`.
+/// The `ntuple` player plays the network passed as `net`.
+#[pyclass(name = "Player", module = "solve2048")]
+struct PyPlayer {
+    config: PlayerConfig,
+    net: Option<Py<PyNTuple>>,
+}
+
+#[pymethods]
+impl PyPlayer {
+    #[new]
+    #[pyo3(signature = (name = "expectimax", net = None, **config))]
+    fn new(
+        name: &str,
+        net: Option<Py<PyNTuple>>,
+        config: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Self> {
+        let options = match config {
+            Some(config) => match json(config.as_any())? {
+                Value::Object(options) => options,
+                _ => unreachable!("a dict is an object"),
+            },
+            None => Map::new(),
+        };
+        let config = PlayerConfig::from_options(name, &options).map_err(value_error)?;
+        if config == PlayerConfig::NTuple && net.is_none() {
+            return Err(value_error("the ntuple player needs a net"));
+        }
+        Ok(Self { config, net })
+    }
+
+    /// `(move, scores)`: the move, -1 once the game is over, and the score
+    /// of each move by number, None for illegal ones.
+    fn choose(&self, py: Python<'_>, board: &PyBoard) -> (i32, [Option<f32>; 4]) {
+        let net = self.net.as_ref().map(|n| n.borrow(py));
+        let net = net.as_deref().map(|n| &n.0);
+        let b = board.0;
+        let config = &self.config;
+        let choice = py.detach(|| config.choice(&b, net).expect("net checked in new"));
+        (choice.mv, choice.scores)
+    }
+
+    /// `choose` for many boards at once: an array of moves, and a row of
+    /// scores per board with NaN for illegal moves.
+    fn choose_batch<'py>(
+        &self,
+        py: Python<'py>,
+        boards: PyReadonlyArray1<'py, u64>,
+    ) -> PyResult<ChoiceArrays<'py>> {
+        let boards = boards.as_slice()?;
+        let net = self.net.as_ref().map(|n| n.borrow(py));
+        let net = net.as_deref().map(|n| &n.0);
+        let config = &self.config;
+        let choices: Vec<_> = py.detach(|| {
+            boards
+                .par_iter()
+                .map(|&raw| {
+                    config
+                        .choice(&Board::from_raw(raw), net)
+                        .expect("net checked in new")
+                })
+                .collect()
+        });
+        let moves = choices.iter().map(|c| c.mv).collect::<Vec<_>>();
+        let scores = choices
+            .iter()
+            .flat_map(|c| c.scores.map(|s| s.unwrap_or(f32::NAN)))
+            .collect();
+        Ok((moves.into_pyarray(py), rows(py, 4, scores)))
+    }
+}
+
+/// A Python value as JSON, for configs.
+fn json(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if obj.is_none() {
+        Ok(Value::Null)
+    } else if obj.is_instance_of::<PyBool>() {
+        Ok(Value::Bool(obj.extract()?))
+    } else if let Ok(i) = obj.extract::<i64>() {
+        Ok(i.into())
+    } else if let Ok(f) = obj.extract::<f64>() {
+        Ok(f.into())
+    } else if let Ok(s) = obj.extract::<String>() {
+        Ok(s.into())
+    } else if let Ok(dict) = obj.cast::<PyDict>() {
+        let mut map = Map::new();
+        for (k, v) in dict {
+            map.insert(k.extract()?, json(&v)?);
+        }
+        Ok(Value::Object(map))
+    } else if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
+        Ok(Value::Array(
+            obj.try_iter()?
+                .map(|v| json(&v?))
+                .collect::<PyResult<_>>()?,
+        ))
+    } else {
+        Err(value_error(format!("{obj} can't be a config value")))
+    }
+}
+
+#[pymodule]
+fn solve2048(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBoard>()?;
+    m.add_class::<PyVecEnv>()?;
+    m.add_class::<PyNTuple>()?;
+    m.add_class::<PyPlayer>()?;
+    m.add("MOVES", [0, 1, 2, 3].map(|i| Move::from_int(i).to_string()))?;
+    m.add("PLAYERS", PlayerConfig::NAMES)?;
+    Ok(())
+}
@@ -0,0 +1,260 @@
+//! Saved games: everything needed to replay one move by move.
+//!
+//! A record is serialised with serde, with each turn as a short token: the
+//! move's initial, then two hex digits (cell, exponent) per tile spawned after
+//! it, then optionally `@` and the player's evaluation. `"L5190@1834.5"` is a
+//! left move after which a 2 spawned in cell 5 and another in cell 9, with
+//! the player valuing the position at 1834.5. The opening tiles are written
+//! the same way without a move.
+use crate::{GameBoard, Move, ParseError, SpawnRules};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// A tile placed on the board: a 4x4 cell in reading order and an exponent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Spawn {
+    pub cell: u8,
+    pub exponent: u8,
+}
+
+/// A move and the tiles that spawned after it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Turn {
+    pub mv: Move,
+    pub spawns: Vec<Spawn>,
+    /// The player's value for the position it moved from, if it gave one.
+    pub eval: Option<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct GameRecord {
+    /// Seed of the spawn generator, if the game was played with one.
+    pub seed: Option<u64>,
+    pub rules: SpawnRules,
+    /// Who played, e.g. `"expectimax"`.
+    pub player: String,
+    /// How the player was set up, e.g. `"depth=3"`.
+    pub config: String,
+    #[serde(with = "spawn_list")]
+    pub start: Vec<Spawn>,
+    pub turns: Vec<Turn>,
+    /// Total merge score, checked on replay.
+    pub score: u32,
+}
+
+impl GameRecord {
+    pub fn new(rules: SpawnRules, seed: Option<u64>) -> Self {
+        Self {
+            seed,
+            rules,
+            player: String::new(),
+            config: String::new(),
+            start: Vec::new(),
+            turns: Vec::new(),
+            score: 0,
+        }
+    }
+
+    pub fn with_player(mut self, player: &str, config: &str) -> Self {
+        self.player = player.to_string();
+        self.config = config.to_string();
+        self
+    }
+
+    /// Replay the game on a 4x4 board of type `B`, returning the board
+    /// before the first move and after every turn. Checks that every move was
+    /// legal, that every tile went on an empty cell and could spawn under the
+    /// rules, and that the score adds up.
+    pub fn replay<B: GameBoard>(&self) -> Result<Vec<B>, ReplayError> {
+        let boards = self.replay_scored()?;
+        Ok(boards.into_iter().map(|(b, _)| b).collect())
+    }
+
+    /// `replay`, with each board paired with the score so far.
+    pub fn replay_scored<B: GameBoard>(&self) -> Result<Vec<(B, u32)>, ReplayError> {
+        if B::SIZE != 4 {
+            return Err(ReplayError::BoardSize(B::SIZE));
+        }
+        let mut b = B::new();
+        self.place(&mut b, &self.start, self.rules.initial, 0)?;
+        let mut boards = vec![(b, 0)];
+        let mut score = 0;
+        for (i, turn) in self.turns.iter().enumerate() {
+            let turn_no = i + 1;
+            score += b
+                .make_move(turn.mv)
+                .ok_or(ReplayError::IllegalMove(turn_no))?;
+            self.place(&mut b, &turn.spawns, self.rules.per_move, turn_no)?;
+            boards.push((b, score));
+        }
+        if score != self.score {
+            return Err(ReplayError::Score {
+                recorded: self.score,
+                replayed: score,
+            });
+        }
+        Ok(boards)
+    }
+
+    /// Put `spawns` on `b`: `count` of them, or as many as there was room for.
+    fn place<B: GameBoard>(
+        &self,
+        b: &mut B,
+        spawns: &[Spawn],
+        count: u8,
+        turn: usize,
+    ) -> Result<(), ReplayError> {
+        for &spawn in spawns {
+            let Spawn { cell, exponent } = spawn;
+            if cell >= 16 || b.at(cell) != 0 || self.rules.prob(exponent) == 0.0 {
+                return Err(ReplayError::BadSpawn { turn, spawn });
+            }
+            b.set(cell, exponent);
+        }
+        if spawns.len() > count as usize || (spawns.len() < count as usize && b.num_empty() > 0) {
+            return Err(ReplayError::SpawnCount(turn));
+        }
+        Ok(())
+    }
+}
+
+/// Where a replay went wrong. Turns count from 1; turn 0 is the opening.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplayError {
+    /// Records are of 4x4 games; this board has another size.
+    BoardSize(u8),
+    IllegalMove(usize),
+    BadSpawn {
+        turn: usize,
+        spawn: Spawn,
+    },
+    /// Too many tiles, or too few while there was still room.
+    SpawnCount(usize),
+    Score {
+        recorded: u32,
+        replayed: u32,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::BoardSize(n) => write!(f, "can't replay a 4x4 game on a {n}x{n} board"),
+            ReplayError::IllegalMove(turn) => write!(f, "turn {turn}: illegal move"),
+            ReplayError::BadSpawn { turn, spawn } => write!(
+                f,
+                "turn {turn}: can't spawn exponent {} in cell {}",
+                spawn.exponent, spawn.cell
+            ),
+            ReplayError::SpawnCount(turn) => write!(f, "turn {turn}: wrong number of spawns"),
+            ReplayError::Score { recorded, replayed } => {
+                write!(f, "recorded score {recorded}, replayed {replayed}")
+            }
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+fn fmt_spawns(spawns: &[Spawn], f: &mut fmt::Formatter) -> fmt::Result {
+    for s in spawns {
+        write!(f, "{:x}{:x}", s.cell, s.exponent)?;
+    }
+    Ok(())
+}
+
+fn parse_spawns(s: &str) -> Result<Vec<Spawn>, ParseError> {
+    let digits: Vec<u8> = s
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()
+        .ok_or_else(|| ParseError::new(format!("bad spawns {s:?}")))?;
+    if !digits.len().is_multiple_of(2) {
+        return Err(ParseError::new(format!("bad spawns {s:?}")));
+    }
+    Ok(digits
+        .chunks(2)
+        .map(|d| Spawn {
+            cell: d[0],
+            exponent: d[1],
+        })
+        .collect())
+}
+
+impl fmt::Display for Turn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let initial = match self.mv {
+            Move::Up => 'U',
+            Move::Down => 'D',
+            Move::Left => 'L',
+            Move::Right => 'R',
+        };
+        write!(f, "{initial}")?;
+        fmt_spawns(&self.spawns, f)?;
+        if let Some(eval) = self.eval {
+            write!(f, "@{eval}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Turn {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let (body, eval) = match s.split_once('@') {
+            Some((body, eval)) => {
+                let eval = eval
+                    .parse()
+                    .map_err(|_| ParseError::new(format!("bad evaluation in {s:?}")))?;
+                (body, Some(eval))
+            }
+            None => (s, None),
+        };
+        let split = body.char_indices().nth(1).map_or(body.len(), |(i, _)| i);
+        let (mv, spawns) = body.split_at(split);
+        Ok(Turn {
+            mv: mv.parse()?,
+            spawns: parse_spawns(spawns)?,
+            eval,
+        })
+    }
+}
+
+impl Serialize for Turn {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Turn {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// The opening tiles as a token without a move.
+mod spawn_list {
+    use super::*;
+
+    struct Spawns<'a>(&'a [Spawn]);
+
+    impl fmt::Display for Spawns<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            fmt_spawns(self.0, f)
+        }
+    }
+
+    pub fn serialize<S: Serializer>(spawns: &[Spawn], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&Spawns(spawns))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Spawn>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        parse_spawns(&s).map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,230 @@
+//! Named n-tuple networks, downloaded once into a local cache and checked
+//! against their SHA-256 on every load.
+//!
+//! A manifest lists the networks:
+//!
+//! ```json
+//! {"models": [{"name": "tuplenet-4m", "url": "https://...", "sha256": "9f2c...",
+//!              "patterns": [[0, 1, 2, 3, 4, 5], [4, 5, 6, 7, 8, 9]], "stages": 1}]}
+//! ```
+//!
+//! Weight files hold `stages` networks back to back, each in the layout of
+//! `NTuple::save_weights`. Fetching goes through a `Fetch` backend, so a
+//! registry without one works from the cache alone.
+use crate::{NTuple, WeightFormat};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// One network in a manifest.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelEntry {
+    pub name: String,
+    pub url: String,
+    /// Hex SHA-256 of the weight file.
+    pub sha256: String,
+    /// The cells of each feature, numbered as `Board` numbers them. Every
+    /// stage has the same features.
+    pub patterns: Vec<Vec<u8>>,
+    /// Networks in the file, one per stage of the game.
+    #[serde(default = "one_stage")]
+    pub stages: u32,
+}
+
+fn one_stage() -> u32 {
+    1
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub models: Vec<ModelEntry>,
+}
+
+impl ModelEntry {
+    /// Whether the name and checksum are safe to build a cache path from:
+    /// manifests come from elsewhere, and a name like `../x` would be
+    /// written outside the cache.
+    pub fn check(&self) -> io::Result<()> {
+        let name = &self.name;
+        if name.is_empty()
+            || name.contains(['/', '\\', '\0'])
+            || name.contains("..")
+            || Path::new(name).is_absolute()
+        {
+            return Err(invalid(format!("{name:?} isn't a plain file name")));
+        }
+        if self.sha256.len() != 64 || !self.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid(format!(
+                "{name}: {:?} isn't a hex SHA-256",
+                self.sha256
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Manifest {
+    /// Parse a manifest, rejecting entries that fail `ModelEntry::check`.
+    pub fn from_json(json: &str) -> io::Result<Self> {
+        let manifest: Self = serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+        for entry in &manifest.models {
+            entry.check()?;
+        }
+        Ok(manifest)
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ModelEntry> {
+        self.models.iter().find(|m| m.name == name)
+    }
+}
+
+/// Where weight files come from when they aren't cached.
+pub trait Fetch {
+    fn fetch(&self, url: &str) -> io::Result<Vec<u8>>;
+}
+
+impl<F: Fn(&str) -> io::Result<Vec<u8>>> Fetch for F {
+    fn fetch(&self, url: &str) -> io::Result<Vec<u8>> {
+        self(url)
+    }
+}
+
+/// A backend that never fetches, for working offline from the cache.
+pub struct Offline;
+
+impl Fetch for Offline {
+    fn fetch(&self, url: &str) -> io::Result<Vec<u8>> {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{url} isn't cached and the registry is offline"),
+        ))
+    }
+}
+
+pub struct Registry {
+    manifest: Manifest,
+    cache_dir: PathBuf,
+    fetch: Box<dyn Fetch>,
+}
+
+impl Registry {
+    /// A registry caching into `cache_dir` and fetching through `fetch`.
+    pub fn new(
+        manifest: Manifest,
+        cache_dir: impl Into<PathBuf>,
+        fetch: impl Fetch + 'static,
+    ) -> Self {
+        Self {
+            manifest,
+            cache_dir: cache_dir.into(),
+            fetch: Box::new(fetch),
+        }
+    }
+
+    /// A registry that only loads what is already in `cache_dir`.
+    pub fn offline(manifest: Manifest, cache_dir: impl Into<PathBuf>) -> Self {
+        Self::new(manifest, cache_dir, Offline)
+    }
+
+    /// `$SOLVE2048_CACHE`, else `solve2048` in the user's cache directory.
+    pub fn default_cache_dir() -> PathBuf {
+        if let Some(dir) = std::env::var_os("SOLVE2048_CACHE") {
+            return dir.into();
+        }
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".cache")))
+            .unwrap_or_else(std::env::temp_dir);
+        base.join("solve2048")
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Where `entry` is cached. The name includes the checksum, so a new
+    /// version of a network doesn't clash with the old one.
+    pub fn cache_path(&self, entry: &ModelEntry) -> PathBuf {
+        let sha = entry.sha256.get(..16).unwrap_or(&entry.sha256);
+        self.cache_dir.join(format!("{}-{}.bin", entry.name, sha))
+    }
+
+    /// The verified weight file of `name`, fetched first if it isn't cached.
+    /// A cached file that fails the checksum is fetched again.
+    pub fn bytes(&self, name: &str) -> io::Result<Vec<u8>> {
+        let entry = self.entry(name)?;
+        let path = self.cache_path(entry);
+        match fs::read(&path) {
+            Ok(bytes) if sha256_hex(&bytes) == entry.sha256.to_lowercase() => return Ok(bytes),
+            Ok(_) | Err(_) => (),
+        }
+        let bytes = self.fetch.fetch(&entry.url)?;
+        let sha = sha256_hex(&bytes);
+        if sha != entry.sha256.to_lowercase() {
+            return Err(invalid(format!(
+                "{name}: checksum {sha} doesn't match the manifest's {}",
+                entry.sha256
+            )));
+        }
+        fs::create_dir_all(&self.cache_dir)?;
+        // write beside the final path and rename, so readers never see half a file
+        let tmp = path.with_extension("part");
+        fs::write(&tmp, &bytes)?;
+        fs::rename(&tmp, &path)?;
+        Ok(bytes)
+    }
+
+    /// The networks of `name`, one per stage.
+    pub fn load(&self, name: &str) -> io::Result<Vec<NTuple>> {
+        let entry = self.entry(name)?;
+        let bytes = self.bytes(name)?;
+        let mut reader = bytes.as_slice();
+        let mut stages = Vec::with_capacity(entry.stages as usize);
+        for stage in 0..entry.stages {
+            let net = NTuple::import(&mut reader, &WeightFormat::native())?;
+            let patterns: Vec<&[u8]> = net.features().iter().map(|f| f.pattern()).collect();
+            if patterns != entry.patterns {
+                return Err(invalid(format!(
+                    "{name}: stage {stage} has patterns {patterns:?}, not {:?}",
+                    entry.patterns
+                )));
+            }
+            stages.push(net);
+        }
+        if !reader.is_empty() {
+            return Err(invalid(format!(
+                "{name}: trailing data after the last stage"
+            )));
+        }
+        Ok(stages)
+    }
+
+    fn entry(&self, name: &str) -> io::Result<&ModelEntry> {
+        let entry = self.manifest.get(name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no network named {name} in the manifest"),
+            )
+        })?;
+        // a manifest built in code hasn't been through `from_json`
+        entry.check()?;
+        Ok(entry)
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -0,0 +1,95 @@
+//! A row of four nibbles and how it slides. `build.rs` compiles this file too,
+//! to tabulate every row's moves for `Board`.
+use std::fmt;
+
+/// Largest exponent a nibble holds (32768).
+pub const MAX_EXPONENT: u8 = 15;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Row {
+    pub raw: u16,
+}
+
+impl fmt::Display for Row {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut board_str = String::new();
+        for x in 0..4 {
+            let value = self.get(x);
+            board_str.push_str(&format!("{} ", value));
+        }
+        board_str.pop(); // Remove the last space
+        board_str.push('\n'); // Remove the last space
+        write!(f, "{}", board_str)
+    }
+}
+
+impl Row {
+    pub const fn new() -> Self {
+        Self { raw: 0 }
+    }
+    pub const fn from_raw(r: u16) -> Self {
+        Self { raw: r }
+    }
+    pub fn get(&self, i: u8) -> u8 {
+        return ((self.raw >> (i * 4)) & 0x0f) as u8;
+    }
+    pub fn set(&mut self, i: u8, r: u8) {
+        let pos = i * 4;
+        let masked_raw = self.raw & !(0xf << pos);
+        let mod_raw = masked_raw | ((r as u16) << pos);
+        self.raw = mod_raw;
+    }
+    /// Slide and merge towards index 0. Two 32768 tiles don't merge, since a
+    /// nibble can't hold 65536; `WideBoard` plays such positions exactly.
+    pub fn shift_left(&self) -> (u32, Self) {
+        let mut score: u32 = 0;
+        let mut top = 0;
+        let mut tmp = 0;
+        let mut row: Self = *self;
+
+        for i in 0..4 {
+            let mut tile = self.get(i);
+            if tile == 0 {
+                continue;
+            }
+            row.set(i, 0);
+            if tmp != 0 {
+                if tile == tmp && tile < MAX_EXPONENT {
+                    tile = tile + 1;
+                    row.set(top, tile);
+                    top += 1;
+                    score += 1 << tile;
+                    tmp = 0;
+                } else {
+                    row.set(top, tmp);
+                    top += 1;
+                    tmp = tile;
+                }
+            } else {
+                tmp = tile;
+            }
+        }
+        if tmp != 0 {
+            row.set(top, tmp);
+        }
+        return (score, row);
+    }
+    pub const fn reverse(&self) -> Self {
+        let raw = self.raw;
+        let p1 = (raw & 0x000f) << 3 * 4;
+        let p2 = (raw & 0x00f0) << 1 * 4;
+        let p3 = (raw & 0x0f00) >> 1 * 4;
+        let p4 = (raw & 0xf000) >> 3 * 4;
+        return Self::from_raw(p1 | p2 | p3 | p4);
+    }
+    /// This row as column 0 of an otherwise empty board.
+    pub const fn to_col(self) -> u64 {
+        let raw = self.raw as u64;
+        (raw & 0x000f) | (raw & 0x00f0) << 12 | (raw & 0x0f00) << 24 | (raw & 0xf000) << 36
+    }
+    pub fn shift_right(&self) -> (u32, Self) {
+        let rev = self.reverse();
+        let (score, shifted) = rev.shift_left();
+        return (score, shifted.reverse());
+    }
+}
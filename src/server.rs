@@ -0,0 +1,289 @@
+//! A small HTTP server answering with the solvers' evaluations as JSON, for
+//! dashboards and pipelines on the same machine.
+//!
+//! - `GET /health`: `{"status": "ok", "version": "0.1.0", "ntuple": true}`,
+//!   where `ntuple` says whether a network is loaded;
+//! - `POST /evaluate`: a position and a player,
+//!   `{"board": "2 0 0 2 / ...", "player": "expectimax", "config": {"depth": 3}}`,
+//!   answered with a `MoveChoice`. `player` is any of `PlayerConfig::NAMES`
+//!   and defaults to expectimax; `config` holds its config's fields;
+//! - `POST /evaluate/batch`: an array of positions as for `/evaluate`,
+//!   answered with an array of choices, or `{"error": ...}` for positions
+//!   that failed;
+//! - `PUT /ntuple`: a weight file in the native format, which replaces the
+//!   network the `ntuple` player uses.
+//!
+//! Anything else is answered with a status code and `{"error": ...}`.
+//! Requests are answered by a fixed number of workers, and requests asking
+//! for more than the server's `ServerLimits` are refused.
+use crate::par::*;
+use crate::{Board, MoveChoice, NTuple, PlayerConfig, WeightFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::io::{self, Read};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::num::NonZeroUsize;
+use std::sync::{mpsc, Mutex, RwLock};
+use std::thread;
+use tiny_http::{Header, Method, Request, Response};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Evaluate {
+    board: Board,
+    #[serde(default = "default_player")]
+    player: String,
+    #[serde(default)]
+    config: Map<String, Value>,
+}
+
+fn default_player() -> String {
+    "expectimax".to_string()
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Outcome {
+    Choice(MoveChoice),
+    Error { error: String },
+}
+
+/// The most one request may ask of the server. Searches past these are
+/// answered with 400, and bodies past them with 413.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ServerLimits {
+    /// Bytes in a JSON request body.
+    pub body: usize,
+    /// Bytes in a weight file for `PUT /ntuple`.
+    pub weights: usize,
+    /// Positions in a batch.
+    pub batch: usize,
+    /// Expectimax plies, whether fixed or the deepest a time budget goes.
+    pub depth: u32,
+    pub time_ms: f64,
+    /// Monte Carlo games per move.
+    pub rollouts: u32,
+    /// Tiles spawned per move in the players' `rules`. Each one multiplies
+    /// the chance nodes a search expands.
+    pub per_move: u8,
+    /// Tiles on the starting board in the players' `rules`.
+    pub initial: u8,
+}
+
+impl Default for ServerLimits {
+    fn default() -> Self {
+        Self {
+            body: 1 << 20,
+            weights: 1 << 30,
+            batch: 256,
+            depth: 10,
+            time_ms: 10_000.0,
+            rollouts: 10_000,
+            per_move: 2,
+            initial: 4,
+        }
+    }
+}
+
+impl ServerLimits {
+    /// Whether a search with `config` stays within these limits.
+    fn check(&self, config: &PlayerConfig) -> Result<(), String> {
+        let over = |what: &str, limit: String| Err(format!("{what} is limited to {limit}"));
+        let rules = match config {
+            PlayerConfig::Expectimax(c) => Some(c.rules),
+            PlayerConfig::MonteCarlo(c) => Some(c.rules),
+            PlayerConfig::NTuple => None,
+        };
+        if let Some(rules) = rules {
+            if rules.per_move > self.per_move {
+                return over("rules.per_move", self.per_move.to_string());
+            }
+            if rules.initial > self.initial {
+                return over("rules.initial", self.initial.to_string());
+            }
+        }
+        match config {
+            PlayerConfig::Expectimax(c) => {
+                if c.depth.is_some_and(|d| d > self.depth) {
+                    return over("depth", self.depth.to_string());
+                }
+                if let Some(ms) = c.time_ms {
+                    if ms > self.time_ms {
+                        return over("time_ms", self.time_ms.to_string());
+                    }
+                    if c.max_depth > self.depth {
+                        return over("max_depth", self.depth.to_string());
+                    }
+                }
+            }
+            PlayerConfig::MonteCarlo(c) if c.rollouts > self.rollouts => {
+                return over("rollouts", self.rollouts.to_string());
+            }
+            PlayerConfig::MonteCarlo(_) | PlayerConfig::NTuple => (),
+        }
+        Ok(())
+    }
+}
+
+pub struct SolverServer {
+    http: tiny_http::Server,
+    net: RwLock<Option<NTuple>>,
+    limits: ServerLimits,
+    workers: usize,
+}
+
+impl SolverServer {
+    /// A server listening on `addr`, e.g. `"127.0.0.1:8048"`, with port 0
+    /// for any free port.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let http = tiny_http::Server::http(addr).map_err(io::Error::other)?;
+        Ok(Self {
+            http,
+            net: RwLock::new(None),
+            limits: ServerLimits::default(),
+            workers: thread::available_parallelism().map_or(4, NonZeroUsize::get),
+        })
+    }
+
+    pub fn with_limits(mut self, limits: ServerLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Answer at most `workers` requests at once, one per core by default.
+    /// Other requests wait their turn.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Serve `net` to the `ntuple` player.
+    pub fn with_ntuple(self, net: NTuple) -> Self {
+        *self.net.write().unwrap() = Some(net);
+        self
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.http
+            .server_addr()
+            .to_ip()
+            .expect("bound to an IP address")
+    }
+
+    /// Answer requests on the workers until `stop` is called.
+    pub fn serve(&self) {
+        let (queue, requests) = mpsc::sync_channel(self.workers);
+        let requests = Mutex::new(requests);
+        thread::scope(|s| {
+            for _ in 0..self.workers {
+                s.spawn(|| loop {
+                    // the lock is released before the request is answered
+                    let next = requests.lock().unwrap().recv();
+                    match next {
+                        Ok(request) => self.respond(request),
+                        Err(_) => break,
+                    }
+                });
+            }
+            for request in self.http.incoming_requests() {
+                // waits while every worker is busy and the queue is full
+                if queue.send(request).is_err() {
+                    break;
+                }
+            }
+            drop(queue);
+        });
+    }
+
+    /// Make `serve` return once the requests in flight are answered.
+    pub fn stop(&self) {
+        self.http.unblock();
+    }
+
+    fn respond(&self, mut request: Request) {
+        let limit = if request.url().starts_with("/ntuple") {
+            self.limits.weights
+        } else {
+            self.limits.body
+        };
+        let mut body = Vec::new();
+        let read = match request.body_length() {
+            Some(len) if len > limit => Ok(len),
+            _ => (request.as_reader())
+                .take(limit as u64 + 1)
+                .read_to_end(&mut body),
+        };
+        let (status, reply) = match read {
+            Ok(len) if len > limit => (413, error(format!("bodies are limited to {limit} bytes"))),
+            Ok(_) => self.route(request.method(), request.url(), &body),
+            Err(e) => (400, error(e)),
+        };
+        let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+        let response = Response::from_string(reply.to_string())
+            .with_status_code(status)
+            .with_header(content_type);
+        // the client may have gone, and there is no one else to tell
+        let _ = request.respond(response);
+    }
+
+    fn route(&self, method: &Method, url: &str, body: &[u8]) -> (u16, Value) {
+        let path = url.split('?').next().unwrap_or(url);
+        let result = match (method, path) {
+            (Method::Get, "/health") => Ok(json!({
+                "status": "ok",
+                "version": env!("CARGO_PKG_VERSION"),
+                "ntuple": self.net.read().unwrap().is_some(),
+            })),
+            (Method::Post, "/evaluate") => serde_json::from_slice(body)
+                .map_err(error)
+                .and_then(|e| self.evaluate(e).map_err(error))
+                .map(|choice| json!(choice)),
+            (Method::Post, "/evaluate/batch") => serde_json::from_slice(body)
+                .map_err(error)
+                .and_then(|batch| self.evaluate_batch(batch).map_err(error))
+                .map(|outcomes| json!(outcomes)),
+            (Method::Put, "/ntuple") => self.load_ntuple(body).map_err(error),
+            (_, "/health" | "/evaluate" | "/evaluate/batch" | "/ntuple") => {
+                return (405, error(format!("{method} isn't allowed on {path}")));
+            }
+            _ => return (404, error(format!("nothing at {path}"))),
+        };
+        match result {
+            Ok(reply) => (200, reply),
+            Err(reply) => (400, reply),
+        }
+    }
+
+    fn evaluate(&self, request: Evaluate) -> Result<MoveChoice, String> {
+        let config = PlayerConfig::from_options(&request.player, &request.config)?;
+        self.limits.check(&config)?;
+        let net = self.net.read().unwrap();
+        config
+            .choice(&request.board, net.as_ref())
+            .ok_or_else(|| "no n-tuple network is loaded".to_string())
+    }
+
+    fn evaluate_batch(&self, batch: Vec<Evaluate>) -> Result<Vec<Outcome>, String> {
+        if batch.len() > self.limits.batch {
+            return Err(format!("batches are limited to {}", self.limits.batch));
+        }
+        Ok(batch
+            .into_par_iter()
+            .map(|request| match self.evaluate(request) {
+                Ok(choice) => Outcome::Choice(choice),
+                Err(error) => Outcome::Error { error },
+            })
+            .collect())
+    }
+
+    fn load_ntuple(&self, mut body: &[u8]) -> Result<Value, String> {
+        let net = NTuple::import(&mut body, &WeightFormat::native()).map_err(|e| e.to_string())?;
+        let features = net.features().len();
+        *self.net.write().unwrap() = Some(net);
+        Ok(json!({"status": "ok", "features": features}))
+    }
+}
+
+fn error(e: impl ToString) -> Value {
+    json!({"error": e.to_string()})
+}
@@ -0,0 +1,228 @@
+use crate::{Board, Evaluator, ExpectimaxPlayer, GameBoard, Heuristic, WideBoard};
+use fastrand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// How new tiles appear: which exponents with which probabilities, how many
+/// per move and how many on the empty starting board.
+///
+/// Serialised as `{"tiles": [[1, 0.9], [2, 0.1]], "per_move": 1, "initial": 2}`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "SpawnRulesRepr", into = "SpawnRulesRepr")]
+pub struct SpawnRules {
+    /// Probability of each exponent, indexed by exponent. Sums to 1.
+    probs: [f32; 16],
+    pub per_move: u8,
+    pub initial: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SpawnRulesRepr {
+    tiles: Vec<(u8, f32)>,
+    per_move: u8,
+    initial: u8,
+}
+
+impl From<SpawnRules> for SpawnRulesRepr {
+    fn from(rules: SpawnRules) -> Self {
+        Self {
+            tiles: rules.tiles().collect(),
+            per_move: rules.per_move,
+            initial: rules.initial,
+        }
+    }
+}
+
+impl TryFrom<SpawnRulesRepr> for SpawnRules {
+    type Error = String;
+
+    fn try_from(repr: SpawnRulesRepr) -> Result<Self, String> {
+        if let Some(&(e, w)) = repr
+            .tiles
+            .iter()
+            .find(|&&(e, w)| !(1..16).contains(&e) || w.is_nan() || w < 0.0)
+        {
+            return Err(format!("can't spawn exponent {e} with weight {w}"));
+        }
+        if repr.tiles.iter().all(|&(_, w)| w == 0.0) {
+            return Err("no tile can spawn".to_string());
+        }
+        Ok(Self::new(&repr.tiles)
+            .with_per_move(repr.per_move)
+            .with_initial(repr.initial))
+    }
+}
+
+impl Default for SpawnRules {
+    fn default() -> Self {
+        Self::CLASSIC
+    }
+}
+
+impl SpawnRules {
+    /// The original game: 90% 2s, 10% 4s, one per move, two to start.
+    pub const CLASSIC: Self = {
+        let mut probs = [0.0; 16];
+        probs[1] = 0.9;
+        probs[2] = 0.1;
+        Self {
+            probs,
+            per_move: 1,
+            initial: 2,
+        }
+    };
+
+    /// Rules spawning exponent `e` with weight `w` for each `(e, w)` in
+    /// `tiles`, one tile per move and two to start. Weights are normalised.
+    pub fn new(tiles: &[(u8, f32)]) -> Self {
+        let mut probs = [0.0; 16];
+        for &(e, w) in tiles {
+            assert!((1..16).contains(&e), "can't spawn exponent {e}");
+            assert!(w >= 0.0, "negative spawn weight {w}");
+            probs[e as usize] += w;
+        }
+        let total: f32 = probs.iter().sum();
+        assert!(total > 0.0, "no tile can spawn");
+        probs.iter_mut().for_each(|p| *p /= total);
+        Self {
+            probs,
+            per_move: 1,
+            initial: 2,
+        }
+    }
+
+    /// Every new tile is a 4.
+    pub fn fours_only() -> Self {
+        Self::new(&[(2, 1.0)])
+    }
+
+    /// Half 2s, 35% 4s and 15% 8s.
+    pub fn hard() -> Self {
+        Self::new(&[(1, 0.5), (2, 0.35), (3, 0.15)])
+    }
+
+    pub fn with_per_move(mut self, per_move: u8) -> Self {
+        self.per_move = per_move;
+        self
+    }
+
+    pub fn with_initial(mut self, initial: u8) -> Self {
+        self.initial = initial;
+        self
+    }
+
+    /// The exponents that can spawn and their probabilities.
+    pub fn tiles(&self) -> impl Iterator<Item = (u8, f32)> + '_ {
+        (0..16u8)
+            .map(|e| (e, self.probs[e as usize]))
+            .filter(|&(_, p)| p > 0.0)
+    }
+
+    pub fn prob(&self, exponent: u8) -> f32 {
+        self.probs.get(exponent as usize).copied().unwrap_or(0.0)
+    }
+
+    /// Draw the exponent of a new tile.
+    pub fn sample(&self, rng: &mut Rng) -> u8 {
+        let mut x = rng.f32();
+        let mut last = 0;
+        for (e, p) in self.tiles() {
+            if x < p {
+                return e;
+            }
+            x -= p;
+            last = e;
+        }
+        // only reachable through rounding
+        last
+    }
+}
+
+/// Whoever places the new tiles.
+pub trait Spawner<B: GameBoard = Board> {
+    /// The rules this spawner plays by: how many tiles per move and to start.
+    fn rules(&self) -> &SpawnRules;
+
+    /// Choose a cell and an exponent for a new tile. `b` has an empty cell.
+    fn place(&mut self, b: &B) -> (u8, u8);
+}
+
+/// The usual game: tiles drawn from `rules` on uniformly random empty cells.
+pub struct RandomSpawner {
+    rules: SpawnRules,
+    rng: Rng,
+}
+
+impl RandomSpawner {
+    pub fn new(rules: SpawnRules, rng: Rng) -> Self {
+        Self { rules, rng }
+    }
+}
+
+impl<B: GameBoard> Spawner<B> for RandomSpawner {
+    fn rules(&self) -> &SpawnRules {
+        &self.rules
+    }
+
+    fn place(&mut self, b: &B) -> (u8, u8) {
+        let empty: Vec<u8> = (0..B::cells()).filter(|&i| b.at(i) == 0).collect();
+        let cell = empty[self.rng.usize(0..empty.len())];
+        (cell, self.rules.sample(&mut self.rng))
+    }
+}
+
+/// "Evil 2048": every tile goes wherever, and is whichever the rules allow,
+/// that leaves the player worst off according to a minimax search.
+pub struct AdversarialSpawner<E = Heuristic> {
+    search: ExpectimaxPlayer<E>,
+    depth: u32,
+}
+
+impl Default for AdversarialSpawner {
+    fn default() -> Self {
+        Self::new(Heuristic)
+    }
+}
+
+impl<E: Evaluator> AdversarialSpawner<E> {
+    /// An adversary judging positions by `eval`, looking one player move ahead.
+    pub fn new(eval: E) -> Self {
+        Self {
+            search: ExpectimaxPlayer::minimax(eval),
+            depth: 1,
+        }
+    }
+
+    /// Search `depth` player moves ahead of each placement.
+    pub fn with_depth(mut self, depth: u32) -> Self {
+        self.depth = depth.max(1);
+        self
+    }
+
+    pub fn with_rules(mut self, rules: SpawnRules) -> Self {
+        self.search = self.search.with_rules(rules);
+        self
+    }
+}
+
+impl<E: Evaluator> Spawner<Board> for AdversarialSpawner<E> {
+    fn rules(&self) -> &SpawnRules {
+        self.search.rules()
+    }
+
+    fn place(&mut self, b: &Board) -> (u8, u8) {
+        self.search
+            .worst_spawn(b, self.depth)
+            .expect("no empty cell to spawn on")
+    }
+}
+
+impl<E: Evaluator> Spawner<WideBoard> for AdversarialSpawner<E> {
+    fn rules(&self) -> &SpawnRules {
+        self.search.rules()
+    }
+
+    /// Tiles past 32768 are searched as 32768, like players see them.
+    fn place(&mut self, b: &WideBoard) -> (u8, u8) {
+        Spawner::<Board>::place(self, &b.to_board_saturating())
+    }
+}
@@ -0,0 +1,236 @@
+//! A game of 2048 for the web page to drive, so that it plays by exactly the
+//! rules the solvers search.
+use crate::game_board::line_cell;
+use crate::{Board, GameRecord, Move, RandomSpawner, Spawn, SpawnRules, Spawner, Turn};
+use fastrand::Rng;
+use std::error::Error;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// Where one tile went in a move. `merged` tiles end up inside another
+/// tile at `to`, which doubles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileMove {
+    pub from: u8,
+    pub to: u8,
+    pub merged: bool,
+}
+
+/// Where every tile of `b` goes when sliding in direction `m`. Tiles that
+/// stay put are included, with `from == to`.
+pub fn tile_moves(b: &Board, m: Move) -> Vec<TileMove> {
+    let mut moves = Vec::new();
+    for l in 0..4 {
+        let cell = |k: u8| line_cell(4, m, l, k);
+        let mut top = 0;
+        // tile waiting for a partner, and where it came from
+        let mut waiting: Option<(u8, u8)> = None;
+        for k in 0..4 {
+            let tile = b.at(cell(k));
+            if tile == 0 {
+                continue;
+            }
+            match waiting {
+                Some((w, from)) if w == tile && tile < Board::MAX_EXPONENT => {
+                    moves.push(TileMove {
+                        from,
+                        to: cell(top),
+                        merged: false,
+                    });
+                    moves.push(TileMove {
+                        from: cell(k),
+                        to: cell(top),
+                        merged: true,
+                    });
+                    top += 1;
+                    waiting = None;
+                }
+                Some((_, from)) => {
+                    moves.push(TileMove {
+                        from,
+                        to: cell(top),
+                        merged: false,
+                    });
+                    top += 1;
+                    waiting = Some((tile, cell(k)));
+                }
+                None => waiting = Some((tile, cell(k))),
+            }
+        }
+        if let Some((_, from)) = waiting {
+            moves.push(TileMove {
+                from,
+                to: cell(top),
+                merged: false,
+            });
+        }
+    }
+    moves
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct WasmGame {
+    board: Board,
+    spawner: RandomSpawner,
+    record: GameRecord,
+    /// Board and score before each turn, for undo.
+    history: Vec<(Board, u32)>,
+    last_moves: Vec<TileMove>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl WasmGame {
+    /// A new classic game with its opening tiles. Spawns are reproducible
+    /// given a `seed`.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(seed: Option<u64>) -> WasmGame {
+        let rules = SpawnRules::CLASSIC;
+        let rng = seed.map_or_else(Rng::new, Rng::with_seed);
+        let mut game = WasmGame {
+            board: Board::new(),
+            spawner: RandomSpawner::new(rules, rng),
+            record: GameRecord::new(rules, seed).with_player("human", ""),
+            history: Vec::new(),
+            last_moves: Vec::new(),
+        };
+        for _ in 0..rules.initial {
+            let spawn = game.spawn_one();
+            game.record.start.extend(spawn);
+        }
+        game
+    }
+
+    /// The whole game so far as a JSON game record.
+    pub fn save(&self) -> String {
+        serde_json::to_string(&self.record).unwrap()
+    }
+
+    /// Exponents of the 16 cells in reading order, 0 for empty.
+    pub fn cells(&self) -> Vec<u8> {
+        (0..16).map(|i| self.board.at(i)).collect()
+    }
+
+    pub fn score(&self) -> u32 {
+        self.record.score
+    }
+
+    /// The legal directions, numbered as for `move`.
+    pub fn legal_moves(&self) -> Vec<i32> {
+        self.board
+            .legal_moves()
+            .iter()
+            .map(|m| m.to_int())
+            .collect()
+    }
+
+    pub fn over(&self) -> bool {
+        self.board.game_ended()
+    }
+
+    pub fn max_tile(&self) -> u32 {
+        self.board.max_tile()
+    }
+
+    /// Take back the last move and the tiles that spawned after it. Returns
+    /// false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some((board, score)) = self.history.pop() else {
+            return false;
+        };
+        self.board = board;
+        self.record.turns.pop();
+        self.record.score = score;
+        self.last_moves.clear();
+        true
+    }
+
+    /// How the tiles moved in the last move, as `(from, to, merged)` triples
+    /// of cells with `merged` 0 or 1, for animating it.
+    pub fn last_moves(&self) -> Vec<u8> {
+        self.last_moves
+            .iter()
+            .flat_map(|t| [t.from, t.to, t.merged as u8])
+            .collect()
+    }
+
+    /// The tiles that spawned after the last move as `(cell, exponent)`
+    /// pairs, or the opening tiles before any move.
+    pub fn last_spawns(&self) -> Vec<u8> {
+        let spawns = match self.record.turns.last() {
+            Some(turn) => &turn.spawns,
+            None => &self.record.start,
+        };
+        spawns.iter().flat_map(|s| [s.cell, s.exponent]).collect()
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+impl WasmGame {
+    /// Restore a game saved with `save`, checking that it replays.
+    #[wasm_bindgen(js_name = load)]
+    pub fn load_js(json: &str) -> Result<WasmGame, JsError> {
+        Self::load(json).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Slide in direction `dir` (0 up, 1 right, 2 down, 3 left) and spawn
+    /// the next tile. Returns false, changing nothing, if the move is illegal.
+    #[wasm_bindgen(js_name = move)]
+    pub fn make_move(&mut self, dir: u32) -> Result<bool, JsError> {
+        if dir > 3 {
+            return Err(JsError::new(&format!("{dir} is not a direction")));
+        }
+        Ok(self.play(Move::from_int(dir)))
+    }
+}
+
+impl WasmGame {
+    /// Restore a game saved with `save`, checking that it replays.
+    pub fn load(json: &str) -> Result<WasmGame, Box<dyn Error + Send + Sync>> {
+        let record: GameRecord = serde_json::from_str(json)?;
+        let mut history = record.replay_scored::<Board>()?;
+        let (board, _) = history.pop().unwrap();
+        // the spawns after loading aren't the ones the seed would give
+        let rng = Rng::new();
+        Ok(WasmGame {
+            board,
+            spawner: RandomSpawner::new(record.rules, rng),
+            record,
+            history,
+            last_moves: Vec::new(),
+        })
+    }
+
+    pub fn board(&self) -> Board {
+        self.board
+    }
+
+    /// Make `m` if it's legal, spawning and recording the turn.
+    pub fn play(&mut self, m: Move) -> bool {
+        let before = self.board;
+        let Some(score) = self.board.make_move(m) else {
+            return false;
+        };
+        self.last_moves = tile_moves(&before, m);
+        self.history.push((before, self.record.score));
+        let spawns = (0..self.record.rules.per_move)
+            .filter_map(|_| self.spawn_one())
+            .collect();
+        self.record.turns.push(Turn {
+            mv: m,
+            spawns,
+            eval: None,
+        });
+        self.record.score += score;
+        true
+    }
+
+    fn spawn_one(&mut self) -> Option<Spawn> {
+        if self.board.num_empty() == 0 {
+            return None;
+        }
+        let (cell, exponent) = self.spawner.place(&self.board);
+        self.board.set(cell, exponent);
+        Some(Spawn { cell, exponent })
+    }
+}
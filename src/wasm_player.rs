@@ -0,0 +1,147 @@
+//! The players as the web page calls them: fixed players taking a board of
+//! 16 cells, and players configured by a plain JS object, e.g.
+//! `new WasmExpectimax({time_ms: 50, heuristic: {empty_weight: 300}})`, that
+//! answer with `{move: 3, scores: [null, 812.5, null, 1040.2]}`.
+use crate::{
+    Board, ConfigHeuristic, ExpectimaxConfig, ExpectimaxPlayer, MonteCarloConfig, MonteCarloPlayer,
+    MoveChoice, NTuple, Player,
+};
+use fastrand::Rng;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+extern "C" {
+    fn alert(s: &str);
+}
+
+/// The board passed from JS: 16 cells in reading order, exponents unless
+/// `tiles` is true.
+fn board_arg(arr: &[i32], tiles: Option<bool>) -> Result<Board, JsError> {
+    if tiles.unwrap_or(false) {
+        Ok(Board::try_from_tiles(arr)?)
+    } else {
+        Ok(Board::try_from_exponents(arr)?)
+    }
+}
+
+/// How many threads the solvers search with. In the browser that is the
+/// worker pool's size once `initThreadPool` has run, and 1 without a pool.
+#[wasm_bindgen]
+pub fn solver_threads() -> usize {
+    #[cfg(feature = "rayon")]
+    return rayon::current_num_threads();
+    #[cfg(not(feature = "rayon"))]
+    return 1;
+}
+
+#[wasm_bindgen]
+pub fn monte_carlo(arr: &[i32], tiles: Option<bool>) -> Result<i32, JsError> {
+    let b = board_arg(arr, tiles)?;
+    let next_move = MonteCarloPlayer::default().next_move(&b);
+    Ok(match next_move {
+        Some(m) => m.to_int(),
+        None => -1,
+    })
+}
+
+#[wasm_bindgen]
+pub fn expectimax(arr: &[i32], tiles: Option<bool>) -> Result<i32, JsError> {
+    let b = board_arg(arr, tiles)?;
+    let next_move = ExpectimaxPlayer::default().next_move(&b);
+    Ok(match next_move {
+        Some(m) => m.to_int(),
+        None => -1,
+    })
+}
+
+#[wasm_bindgen]
+pub fn build_ntuple(weights: &[u8]) -> NTuple {
+    console_error_panic_hook::set_once();
+    let mut net = NTuple::default();
+    net.load_weights(&mut weights.as_ref());
+    net
+}
+
+#[wasm_bindgen]
+pub fn ntuple(net: &NTuple, arr: &[i32], tiles: Option<bool>) -> Result<i32, JsError> {
+    // statically load the network weights
+    let b = board_arg(arr, tiles)?;
+    let next_move = net.next_move(&b);
+    Ok(match next_move {
+        Some(m) => m.to_int(),
+        None => -1,
+    })
+}
+
+#[wasm_bindgen]
+pub fn random_available_move(arr: &[i32], tiles: Option<bool>) -> Result<i32, JsError> {
+    let available = board_arg(arr, tiles)?.legal_moves();
+    if available.is_empty() {
+        return Ok(-1);
+    }
+
+    let mut rng = Rng::new();
+    let i = rng.usize(0..available.len());
+    Ok(available.iter().nth(i).unwrap().to_int())
+}
+
+#[wasm_bindgen]
+pub struct WasmMonteCarlo {
+    player: MonteCarloPlayer,
+}
+
+#[wasm_bindgen]
+impl WasmMonteCarlo {
+    /// A player set up by a `MonteCarloConfig` object, or the defaults.
+    #[wasm_bindgen(constructor)]
+    pub fn new(config: JsValue) -> Result<WasmMonteCarlo, JsError> {
+        let config: MonteCarloConfig = config_arg(config)?;
+        Ok(Self {
+            player: config.player(),
+        })
+    }
+
+    /// Choose a move for the board `arr`, given as for `monte_carlo`. Scores
+    /// are average rollout results.
+    pub fn choose(&self, arr: &[i32], tiles: Option<bool>) -> Result<JsValue, JsError> {
+        to_js(&self.player.choice(&board_arg(arr, tiles)?))
+    }
+}
+
+#[wasm_bindgen]
+pub struct WasmExpectimax {
+    player: ExpectimaxPlayer<ConfigHeuristic>,
+}
+
+#[wasm_bindgen]
+impl WasmExpectimax {
+    /// A player set up by an `ExpectimaxConfig` object, or the defaults.
+    #[wasm_bindgen(constructor)]
+    pub fn new(config: JsValue) -> Result<WasmExpectimax, JsError> {
+        let config: ExpectimaxConfig = config_arg(config)?;
+        Ok(Self {
+            player: config.player(),
+        })
+    }
+
+    /// Choose a move for the board `arr`, given as for `expectimax`. Scores
+    /// are search values.
+    pub fn choose(&self, arr: &[i32], tiles: Option<bool>) -> Result<JsValue, JsError> {
+        to_js(&self.player.choice(&board_arg(arr, tiles)?))
+    }
+}
+
+/// A config object from JS, with `undefined` or `null` meaning the defaults.
+fn config_arg<T: Default + for<'de> Deserialize<'de>>(config: JsValue) -> Result<T, JsError> {
+    if config.is_undefined() || config.is_null() {
+        return Ok(T::default());
+    }
+    Ok(serde_wasm_bindgen::from_value(config)?)
+}
+
+fn to_js(choice: &MoveChoice) -> Result<JsValue, JsError> {
+    // null rather than undefined for the illegal moves
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    Ok(choice.serialize(&serializer)?)
+}
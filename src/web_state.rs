@@ -0,0 +1,129 @@
+//! The game state the web page keeps in localStorage (see
+//! `www/local_storage_manager.js`), so a browser game can be analysed here
+//! and a position from here played on in the browser.
+//!
+//! The page stores the grid column by column: `cells[x][y]` is the tile in
+//! column `x` of row `y`, or `null`, and tiles hold their values, not
+//! exponents.
+use crate::GameBoard;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebState {
+    pub grid: WebGrid,
+    pub score: u32,
+    pub over: bool,
+    pub won: bool,
+    /// The player chose to go on after reaching 2048.
+    pub keep_playing: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebGrid {
+    pub size: u8,
+    pub cells: Vec<Vec<Option<WebTile>>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebTile {
+    pub position: WebPosition,
+    pub value: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebPosition {
+    pub x: u8,
+    pub y: u8,
+}
+
+/// Why a `WebState` doesn't describe a board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebStateError {
+    /// The grid isn't `expected` cells a side.
+    Size { expected: u8, got: usize },
+    /// The tile in column `x` of row `y` has a value the board can't hold.
+    Tile { x: u8, y: u8, value: u32 },
+    /// The tile in column `x` of row `y` claims to be somewhere else.
+    Position { x: u8, y: u8 },
+}
+
+impl fmt::Display for WebStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WebStateError::Size { expected, got } => {
+                write!(f, "expected a grid of size {expected}, got {got}")
+            }
+            WebStateError::Tile { x, y, value } => {
+                write!(f, "cell ({x}, {y}) can't hold {value}")
+            }
+            WebStateError::Position { x, y } => {
+                write!(f, "the tile in cell ({x}, {y}) has another position")
+            }
+        }
+    }
+}
+
+impl Error for WebStateError {}
+
+impl WebState {
+    /// The state of a game at `b` with `score` points. It is over when no
+    /// move is left, and won once a 2048 is on the board, in which case the
+    /// page keeps playing rather than stopping at the win screen.
+    pub fn from_board<B: GameBoard>(b: &B, score: u32) -> Self {
+        let n = B::SIZE;
+        let cells = (0..n)
+            .map(|x| {
+                (0..n)
+                    .map(|y| match b.get(y, x) {
+                        0 => None,
+                        e => Some(WebTile {
+                            position: WebPosition { x, y },
+                            value: 1 << e,
+                        }),
+                    })
+                    .collect()
+            })
+            .collect();
+        let won = b.log_max_tile() >= 11;
+        Self {
+            grid: WebGrid { size: n, cells },
+            score,
+            over: b.game_ended(),
+            won,
+            keep_playing: won,
+        }
+    }
+
+    /// The board on the page. The score is in `score`.
+    pub fn board<B: GameBoard>(&self) -> Result<B, WebStateError> {
+        let n = B::SIZE;
+        let size = |got: usize| WebStateError::Size { expected: n, got };
+        if self.grid.size != n {
+            return Err(size(self.grid.size as usize));
+        }
+        if self.grid.cells.len() != n as usize {
+            return Err(size(self.grid.cells.len()));
+        }
+        let mut b = B::new();
+        for (x, column) in (0..n).zip(&self.grid.cells) {
+            if column.len() != n as usize {
+                return Err(size(column.len()));
+            }
+            for (y, tile) in (0..n).zip(column) {
+                let Some(tile) = tile else { continue };
+                if tile.position != (WebPosition { x, y }) {
+                    return Err(WebStateError::Position { x, y });
+                }
+                let value = tile.value;
+                if value < 2 || !value.is_power_of_two() || value.ilog2() > B::MAX_EXPONENT as u32 {
+                    return Err(WebStateError::Tile { x, y, value });
+                }
+                b.set(y * n + x, value.ilog2() as u8);
+            }
+        }
+        Ok(b)
+    }
+}
@@ -0,0 +1,33 @@
+//! The engine protocol as a front end sees it: through a subprocess.
+#![cfg(feature = "cli")]
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+#[test]
+fn engine_subprocess() {
+    let mut engine = Command::new(env!("CARGO_BIN_EXE_solve2048-cli"))
+        .arg("engine")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = engine.stdin.take().unwrap();
+    let mut stdout = BufReader::new(engine.stdout.take().unwrap()).lines();
+
+    // answers come as each command is read, not when input ends
+    writeln!(stdin, "isready").unwrap();
+    assert_eq!(stdout.next().unwrap().unwrap(), "readyok");
+    writeln!(stdin, "setoption player expectimax depth 2").unwrap();
+    writeln!(stdin, "position 2 0 0 2 / 0 0 0 0 / 0 0 0 0 / 0 0 0 0").unwrap();
+    writeln!(stdin, "go movetime 20").unwrap();
+    let reply: Vec<String> = stdout
+        .by_ref()
+        .map(Result::unwrap)
+        .take_while(|l| !l.starts_with("bestmove"))
+        .collect();
+    assert!(reply.iter().any(|l| l.starts_with("info move left score ")));
+
+    writeln!(stdin, "quit").unwrap();
+    assert!(engine.wait().unwrap().success());
+}
@@ -0,0 +1,56 @@
+//! The registry against a stand-in for the model host, served locally.
+#![cfg(feature = "huggingface")]
+
+use solve2048::huggingface::HttpFetch;
+use solve2048::{sha256_hex, Feature, Manifest, ModelEntry, NTuple, Registry, WeightFormat};
+use std::thread;
+use tiny_http::{Response, Server};
+
+#[test]
+fn fetch_over_http() {
+    let net = NTuple::new(vec![Feature::from_weights(
+        &[0, 1],
+        (0..256).map(|i| i as f32).collect(),
+    )]);
+    let mut bytes = Vec::new();
+    net.export(&mut bytes, &WeightFormat::native()).unwrap();
+
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let addr = server.server_addr().to_ip().unwrap();
+    let served = bytes.clone();
+    let host = thread::spawn(move || {
+        // one request for the model, one for a file that isn't there
+        for request in server.incoming_requests().take(2) {
+            let response = if request.url() == "/pair.bin" {
+                Response::from_data(served.clone())
+            } else {
+                Response::from_data(Vec::new()).with_status_code(404)
+            };
+            request.respond(response).unwrap();
+        }
+    });
+
+    let entry = |name: &str| ModelEntry {
+        name: name.to_string(),
+        url: format!("http://{addr}/{name}.bin"),
+        sha256: sha256_hex(&bytes),
+        patterns: vec![vec![0, 1]],
+        stages: 1,
+    };
+    let manifest = Manifest {
+        models: vec![entry("pair"), entry("gone")],
+    };
+    let dir = std::env::temp_dir().join(format!("solve2048-http-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let registry = Registry::new(manifest.clone(), &dir, HttpFetch::default());
+
+    let nets = registry.load("pair").unwrap();
+    assert_eq!(nets[0].features()[0].weights()[255], 255.0);
+    assert!(registry.load("gone").is_err());
+    host.join().unwrap();
+
+    // the server is gone, but the cache isn't
+    let offline = Registry::offline(manifest, &dir);
+    assert_eq!(offline.load("pair").unwrap().len(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
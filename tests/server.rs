@@ -0,0 +1,166 @@
+//! The solver server, driven by a bare HTTP client over localhost.
+#![cfg(feature = "server")]
+
+use serde_json::{json, Value};
+use solve2048::{Feature, NTuple, ServerLimits, SolverServer, WeightFormat};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+fn request(addr: SocketAddr, method: &str, path: &str, body: &[u8]) -> (u16, Value) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )
+    .unwrap();
+    stream.write_all(body).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+fn post(addr: SocketAddr, path: &str, body: Value) -> (u16, Value) {
+    request(addr, "POST", path, body.to_string().as_bytes())
+}
+
+#[test]
+fn solver_server() {
+    let server = Arc::new(SolverServer::bind("127.0.0.1:0").unwrap());
+    let addr = server.addr();
+    let serving = thread::spawn({
+        let server = server.clone();
+        move || server.serve()
+    });
+
+    let (status, health) = request(addr, "GET", "/health", b"");
+    assert_eq!(status, 200);
+    assert_eq!(health["status"], "ok");
+    assert_eq!(health["ntuple"], false);
+
+    let board = "2 2 0 0 / 0 0 0 0 / 0 0 0 0 / 0 0 0 4";
+    let (status, choice) = post(
+        addr,
+        "/evaluate",
+        json!({"board": board, "config": {"depth": 1}}),
+    );
+    assert_eq!(status, 200);
+    let best = choice["move"].as_u64().unwrap() as usize;
+    assert!(choice["scores"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|s| s.as_f64().unwrap() <= choice["scores"][best].as_f64().unwrap()));
+
+    // without a network the n-tuple player fails, the others don't
+    let batch = json!([
+        {"board": board, "player": "monte_carlo", "config": {"rollouts": 5}},
+        {"board": board, "player": "ntuple"},
+        {"board": "exp: 1 2 1 2 / 2 1 2 1 / 1 2 1 2 / 2 1 2 1"},
+    ]);
+    let (status, results) = post(addr, "/evaluate/batch", batch.clone());
+    assert_eq!(status, 200);
+    assert!(results[0]["move"].is_number());
+    assert_eq!(results[1]["error"], "no n-tuple network is loaded");
+    assert_eq!(results[2]["move"], -1);
+
+    let net = NTuple::new(vec![Feature::from_weights(
+        &[0],
+        (0..16).map(|i| i as f32).collect(),
+    )]);
+    let mut weights = Vec::new();
+    net.export(&mut weights, &WeightFormat::native()).unwrap();
+    let (status, loaded) = request(addr, "PUT", "/ntuple", &weights);
+    assert_eq!((status, loaded["features"].clone()), (200, json!(1)));
+    let (_, results) = post(addr, "/evaluate/batch", batch);
+    assert!(results[1]["move"].is_number());
+    assert_eq!(request(addr, "GET", "/health", b"").1["ntuple"], true);
+
+    // bad requests say what was wrong
+    let (status, reply) = post(
+        addr,
+        "/evaluate",
+        json!({"board": board, "player": "oracle"}),
+    );
+    assert_eq!(
+        (status, reply["error"].clone()),
+        (400, json!("no player named \"oracle\""))
+    );
+    assert_eq!(post(addr, "/evaluate", json!({"board": "2 2"})).0, 400);
+    assert_eq!(request(addr, "GET", "/evaluate", b"").0, 405);
+    assert_eq!(request(addr, "GET", "/nowhere", b"").0, 404);
+
+    server.stop();
+    serving.join().unwrap();
+}
+
+#[test]
+fn server_limits() {
+    let limits = ServerLimits {
+        body: 256,
+        batch: 2,
+        depth: 3,
+        rollouts: 50,
+        ..ServerLimits::default()
+    };
+    let server = Arc::new(
+        SolverServer::bind("127.0.0.1:0")
+            .unwrap()
+            .with_limits(limits)
+            .with_workers(1),
+    );
+    let addr = server.addr();
+    let serving = thread::spawn({
+        let server = server.clone();
+        move || server.serve()
+    });
+
+    let board = "2 2 0 0 / 0 0 0 0 / 0 0 0 0 / 0 0 0 4";
+    let evaluate =
+        |config: Value| post(addr, "/evaluate", json!({"board": board, "config": config}));
+    assert_eq!(evaluate(json!({"depth": 3})).0, 200);
+    let (status, reply) = evaluate(json!({"depth": 4}));
+    assert_eq!(
+        (status, reply["error"].clone()),
+        (400, json!("depth is limited to 3"))
+    );
+    assert_eq!(evaluate(json!({"time_ms": 100000})).0, 400);
+    assert_eq!(evaluate(json!({"time_ms": 10, "max_depth": 20})).0, 400);
+    let rules = |per_move: u8, initial: u8| json!({"depth": 1, "rules": {"tiles": [[1, 1.0]], "per_move": per_move, "initial": initial}});
+    assert_eq!(evaluate(rules(2, 4)).0, 200);
+    let (status, reply) = evaluate(rules(200, 2));
+    assert_eq!(
+        (status, reply["error"].clone()),
+        (400, json!("rules.per_move is limited to 2"))
+    );
+    assert_eq!(evaluate(rules(1, 200)).0, 400);
+
+    let (status, results) = post(
+        addr,
+        "/evaluate/batch",
+        json!([{"board": board, "player": "monte_carlo", "config": {"rollouts": 51}}]),
+    );
+    assert_eq!(status, 200);
+    assert_eq!(results[0]["error"], "rollouts is limited to 50");
+    let batch = json!([{"board": board}, {"board": board}, {"board": board}]);
+    assert_eq!(post(addr, "/evaluate/batch", batch).0, 400);
+
+    let padding = " ".repeat(300);
+    let body = format!("{{\"board\": \"{board}\"{padding}}}");
+    assert_eq!(request(addr, "POST", "/evaluate", body.as_bytes()).0, 413);
+
+    // one worker still answers requests made together
+    let clients: Vec<_> = (0..4)
+        .map(|_| thread::spawn(move || request(addr, "GET", "/health", b"").0))
+        .collect();
+    for client in clients {
+        assert_eq!(client.join().unwrap(), 200);
+    }
+
+    server.stop();
+    serving.join().unwrap();
+}
@@ -0,0 +1,62 @@
+"""The Python module, built with `maturin develop` and run with pytest."""
+import numpy as np
+import pytest
+
+import solve2048 as s
+
+
+def test_board():
+    b = s.Board("2 2 0 0 / 0 0 0 0 / 0 0 0 0 / 0 0 0 4")
+    assert s.Board.from_exponents(list(b.cells())) == b
+    assert b.cells().dtype == np.uint8
+    left = s.MOVES.index("left")
+    after, score = b.slide(left)
+    assert score == 4 and after.cells()[0] == 2
+    assert b.legal_mask() == [True] * 4
+    with pytest.raises(ValueError):
+        s.Board("2 2")
+
+
+def test_vec_env():
+    env = s.VecEnv(4, "one_hot")
+    obs = env.reset(seed=1)
+    assert obs.shape == (4, 256) and obs.dtype == np.float32
+    assert (obs.sum(axis=1) == 16).all()
+    finished = 0
+    while finished < 4:
+        masks = env.legal_masks()
+        actions = masks.argmax(axis=1).astype(np.int64)
+        obs, rewards, dones, info = env.step(actions)
+        assert obs.shape == (4, 256) and rewards.shape == (4,)
+        assert info["legal"].all()
+        finished += dones.sum()
+
+
+def test_players_and_ntuple(tmp_path):
+    net = s.NTuple([[0, 1, 2, 3], [4, 5, 6, 7]])
+    assert net.patterns == [[0, 1, 2, 3], [4, 5, 6, 7]]
+    for bad in [[], list(range(9)), list(range(16)), [0, 0], [16]]:
+        with pytest.raises(ValueError):
+            s.NTuple([bad])
+    scores = net.train(20, 0.1, seed=3)
+    assert scores.shape == (20,)
+    net.save(str(tmp_path / "net.bin"))
+    loaded = s.NTuple.load(str(tmp_path / "net.bin"))
+
+    env = s.VecEnv(8)
+    env.reset(seed=2)
+    boards = env.boards()
+    assert np.array_equal(net.evaluate(boards), loaded.evaluate(boards))
+
+    for player in [
+        s.Player("expectimax", depth=1),
+        s.Player("monte_carlo", rollouts=5),
+        s.Player("ntuple", net=loaded),
+    ]:
+        moves, scores = player.choose_batch(boards)
+        assert moves.shape == (8,) and scores.shape == (8, 4)
+        legal = env.legal_masks()
+        assert (np.isnan(scores) == ~legal).all()
+        assert legal[np.arange(8), moves].all()
+    with pytest.raises(ValueError):
+        s.Player("expectimax", deep=3)
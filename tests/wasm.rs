@@ -0,0 +1,16 @@
+//! The solvers as the page calls them. Run with
+//! `wasm-pack test --node -- --no-default-features --features wasm`.
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use solve2048::{expectimax, monte_carlo, solver_threads};
+use wasm_bindgen_test::*;
+
+const BOARD: [i32; 16] = [1, 2, 3, 4, 0, 1, 2, 3, 0, 0, 1, 2, 0, 0, 0, 1];
+
+#[wasm_bindgen_test]
+fn solvers_fall_back_to_one_thread() {
+    // nothing started a worker pool, so rayon runs everything here
+    assert_eq!(solver_threads(), 1);
+    assert!((0..4).contains(&expectimax(&BOARD, None).unwrap()));
+    assert!((0..4).contains(&monte_carlo(&BOARD, None).unwrap()));
+}
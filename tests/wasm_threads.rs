@@ -0,0 +1,21 @@
+//! The solvers on a worker pool. Workers need a browser, so run with
+//! `wasm-pack test --headless --chrome -- --no-default-features --features
+//! wasm-threads` using the nightly flags from www/Makefile.
+#![cfg(all(target_arch = "wasm32", feature = "wasm-threads"))]
+
+use solve2048::{expectimax, init_thread_pool, monte_carlo, solver_threads};
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const BOARD: [i32; 16] = [1, 2, 3, 4, 0, 1, 2, 3, 0, 0, 1, 2, 0, 0, 0, 1];
+
+#[wasm_bindgen_test]
+async fn solvers_use_the_pool() {
+    // must come before any solver runs, or rayon settles on one thread
+    JsFuture::from(init_thread_pool(2)).await.unwrap();
+    assert_eq!(solver_threads(), 2);
+    assert!((0..4).contains(&expectimax(&BOARD, None).unwrap()));
+    assert!((0..4).contains(&monte_carlo(&BOARD, None).unwrap()));
+}
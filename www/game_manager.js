@@ -4,23 +4,25 @@ import Grid from './grid';
 import Actuator from './html_actuator';
 import InputManager from './keyboard_input_manager';
 import StorageManager from './local_storage_manager';
+import Tile from './tile';
 
 // convert the below code to ES6 class syntax:
 /**
  * GameManager.
  */
 export default class GameManager {
-    constructor(size, weightsUrl)
+    constructor(size, weightsUrl, solver)
     {
         this.size = size;
+        this.solver = solver;
         this.inputManager = new InputManager;
         this.storageManager = new StorageManager;
         this.actuator = new Actuator;
-        this.startTiles = 2;
         this.botPlaying = false;
         this.delay = 100;
         this.inputManager.on('move', this.move.bind(this));
         this.inputManager.on('restart', this.restart.bind(this));
+        this.inputManager.on('undo', this.undo.bind(this));
         this.inputManager.on('keepPlaying', this.keepPlaying.bind(this));
         this.inputManager.on('randomMove', this.toggleAgent.bind(this));
         this.inputManager.handleDropdownEvent(
@@ -39,6 +41,7 @@ export default class GameManager {
 
         this.perMoveEMA = 0;
 
+        this.game = null;
         this.setup();
     }
 
@@ -95,59 +98,85 @@ export default class GameManager {
 
     isGameTerminated()
     {
-        return this.over || (this.won && !this.keepPlaying);
+        return this.game.over() || (this.won && !this.keepPlaying);
     }
     setup()
     {
+        if (this.game) {
+            this.game.free();
+        }
+        this.game = null;
+        this.won = false;
+        this.keepPlaying = false;
         const previousState = this.storageManager.getGameState();
-        if (previousState) {
-            this.grid =
-                new Grid(previousState.grid.size, previousState.grid.cells);
-            this.score = previousState.score;
-            this.over = previousState.over;
-            this.won = previousState.won;
-            this.keepPlaying = previousState.keepPlaying;
-        } else {
-            this.grid = new Grid(this.size);
-            this.score = 0;
-            this.over = false;
-            this.won = false;
-            this.keepPlaying = false;
-            this.addStartTiles();
+        if (previousState && previousState.record) {
+            try {
+                this.game = wasm.WasmGame.load(previousState.record);
+                this.won = previousState.won;
+                this.keepPlaying = previousState.keepPlaying;
+            } catch (err) {
+                console.warn('Saved game failed to load, starting a new one',
+                             err);
+            }
         }
-        this.actuate();
-    }
-
-
-    addStartTiles()
-    {
-        for (let i = 0; i < this.startTiles; i++) {
-            this.addRandomTile();
+        if (this.game === null) {
+            this.game = new wasm.WasmGame();
         }
+        this.grid = this.gridFromGame(null);
+        this.actuate();
     }
 
-    addRandomTile()
+    // the grid for the game's board. With the exponents from before the
+    // last move, its tiles slide and merge from where they were.
+    gridFromGame(before)
     {
-        if (this.grid.cellsAvailable()) {
-            const value = Math.random() < 0.9 ? 2 : 4;
-            const tile = new Tile(this.grid.randomAvailableCell(), value);
-            this.grid.insertTile(tile);
+        const grid = new Grid(this.size);
+        const position = i =>
+            ({x: i % this.size, y: Math.floor(i / this.size)});
+        const cells = this.game.cells();
+        if (before) {
+            const moves = this.game.last_moves();
+            for (let i = 0; i < moves.length; i += 3) {
+                const [from, to, merged] = moves.subarray(i, i + 3);
+                const tile = new Tile(position(to), 2 ** before[from]);
+                tile.previousPosition = position(from);
+                if (merged) {
+                    const sum = new Tile(position(to), 2 ** cells[to]);
+                    sum.mergedFrom = [grid.cellContent(tile), tile];
+                    grid.insertTile(sum);
+                } else {
+                    grid.insertTile(tile);
+                }
+            }
+            const spawns = this.game.last_spawns();
+            for (let i = 0; i < spawns.length; i += 2) {
+                const cell = position(spawns[i]);
+                grid.insertTile(new Tile(cell, 2 ** spawns[i + 1]));
+            }
         }
+        cells.forEach((exponent, i) => {
+            if (exponent && !grid.cellOccupied(position(i))) {
+                grid.insertTile(new Tile(position(i), 2 ** exponent));
+            }
+        });
+        return grid;
     }
 
     actuate()
     {
-        if (this.storageManager.getBestScore() < this.score) {
-            this.storageManager.setBestScore(this.score);
+        const score = this.game.score();
+        const over = this.game.over();
+        if (this.storageManager.getBestScore() < score) {
+            this.storageManager.setBestScore(score);
         }
-        if (this.over) {
+        if (over) {
             this.storageManager.clearGameState();
         } else {
             this.storageManager.setGameState(this.serialize());
         }
         this.actuator.actuate(this.grid, {
-            score: this.score,
-            over: this.over,
+            score,
+            over,
             won: this.won,
             bestScore: this.storageManager.getBestScore(),
             terminated: this.isGameTerminated()
@@ -157,71 +186,37 @@ export default class GameManager {
     serialize()
     {
         return {
-            grid: this.grid.serialize(),
-            score: this.score,
-            over: this.over,
+            record: this.game.save(),
             won: this.won,
             keepPlaying: this.keepPlaying
         };
     }
 
-    prepareTiles()
-    {
-        this.grid.eachCell((_x, _y, tile) => {
-            if (tile) {
-                tile.mergedFrom = null;
-                tile.savePosition();
-            }
-        });
-    }
-    moveTile(tile, cell)
+    move(direction)
     {
-        this.grid.cells[tile.x][tile.y] = null;
-        this.grid.cells[cell.x][cell.y] = tile;
-        tile.updatePosition(cell);
+        if (this.isGameTerminated()) {
+            return;
+        }
+        const before = this.game.cells();
+        if (!this.game.move(direction)) {
+            return;
+        }
+        this.grid = this.gridFromGame(before);
+        if (this.game.max_tile() >= 2048) {
+            this.won = true;
+        }
+        this.actuate();
     }
 
-    move(direction)
+    undo()
     {
-        if (this.isGameTerminated()) {
+        if (this.botPlaying) {
+            this.inputManager.shakeActivateButton();
             return;
         }
-        const self = this;
-        const vector = this.getVector(direction);
-        const traversals = this.buildTraversals(vector);
-        let moved = false;
-        this.prepareTiles();
-        traversals.x.forEach(x => {
-            traversals.y.forEach(y => {
-                const cell = {x, y};
-                const tile = self.grid.cellContent(cell);
-                if (tile) {
-                    const positions = self.findFarthestPosition(cell, vector);
-                    const next = self.grid.cellContent(positions.next);
-                    if (next && next.value === tile.value && !next.mergedFrom) {
-                        const merged = new Tile(positions.next, tile.value * 2);
-                        merged.mergedFrom = [tile, next];
-                        self.grid.insertTile(merged);
-                        self.grid.removeTile(tile);
-                        tile.updatePosition(positions.next);
-                        self.score += merged.value;
-                        if (merged.value === 2048) {
-                            self.won = true;
-                        }
-                    } else {
-                        self.moveTile(tile, positions.farthest);
-                    }
-                    if (!self.positionsEqual(cell, tile)) {
-                        moved = true;
-                    }
-                }
-            });
-        });
-        if (moved) {
-            this.addRandomTile();
-            if (!this.movesAvailable()) {
-                this.over = true;
-            }
+        if (this.game.undo()) {
+            this.actuator.continueGame();
+            this.grid = this.gridFromGame(null);
             this.actuate();
         }
     }
@@ -232,11 +227,17 @@ export default class GameManager {
         // play until we have lost the game
         // or the user has requested to stop
         // if the win screen shows, keep playing if the player chooses to
-        while (!this.over) {
+        while (!this.game.over()) {
             this.botPlaying = true;
             const arr = this.boardAsArray();
             const start = performance.now();
-            const move = agent(arr);
+            let move;
+            try {
+                move = await agent(arr);
+            } catch (err) {
+                console.error('Solver failed, stopping the bot', err);
+                break;
+            }
             const moveTime = performance.now() - start;
             this.perMoveEMA = moveTime * 0.05 + this.perMoveEMA * 0.95;
             this.inputManager.setMsPerMove(this.perMoveEMA);
@@ -263,12 +264,12 @@ export default class GameManager {
 
     async playMonteCarlo()
     {
-        await this.playGame(arr => wasm.monte_carlo(arr));
+        await this.playGame(arr => this.solver.bestMove('monte_carlo', arr));
     }
 
     async playExpectimax()
     {
-        await this.playGame(arr => wasm.expectimax(arr));
+        await this.playGame(arr => this.solver.bestMove('expectimax', arr));
     }
 
     getDelay(timeTaken)
@@ -353,78 +354,6 @@ export default class GameManager {
 
     boardAsArray()
     {
-        let board = [];
-        for (let i = 0; i < this.size; i++) {
-            for (let j = 0; j < this.size; j++) {
-                let tile = this.grid.cells[j][i];
-                board.push(tile ? Math.log2(tile.value) : 0);
-            }
-        }
-        return board;
-    }
-
-    getVector(direction)
-    {
-        const map = {
-            0: {x: 0, y: -1},  // Up
-            1: {x: 1, y: 0},   // Right
-            2: {x: 0, y: 1},   // Down
-            3: {x: -1, y: 0}   // Left
-        };
-        return map[direction];
-    }
-
-    buildTraversals(vector)
-    {
-        const traversals = {x: [], y: []};
-        for (let pos = 0; pos < this.size; pos++) {
-            traversals.x.push(pos);
-            traversals.y.push(pos);
-        }
-        if (vector.x === 1) traversals.x = traversals.x.reverse();
-        if (vector.y === 1) traversals.y = traversals.y.reverse();
-        return traversals;
-    }
-
-    findFarthestPosition(cell, vector)
-    {
-        let previous;
-        do {
-            previous = cell;
-            cell = {x: previous.x + vector.x, y: previous.y + vector.y};
-        } while (this.grid.withinBounds(cell) && this.grid.cellAvailable(cell));
-        return {farthest: previous, next: cell};
-    }
-
-    movesAvailable()
-    {
-        return this.grid.cellsAvailable() || this.tileMatchesAvailable();
-    }
-
-    tileMatchesAvailable()
-    {
-        let tile;
-        for (let x = 0; x < this.size; x++) {
-            for (let y = 0; y < this.size; y++) {
-                tile = this.grid.cellContent({x, y});
-                if (tile) {
-                    for (let direction = 0; direction < 4; direction++) {
-                        const vector = this.getVector(direction);
-                        const cell = {x: x + vector.x, y: y + vector.y};
-                        const other = this.grid.cellContent(cell);
-                        if (other && other.value === tile.value) {
-                            return true;
-                        }
-                    }
-                }
-            }
-        }
-        return false;
-    }
-
-    positionsEqual(first, second)
-    {
-        return first.x === second.x && first.y === second.y;
+        return Array.from(this.game.cells());
     }
 }
-import Tile from './tile';
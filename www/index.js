@@ -1,17 +1,19 @@
 import init from 'solve2048';
 
 import GameManager from './game_manager';
+import Solver from './solver';
 
 function init_game(wasm_path)
 {
     const weights_url =
         'https://huggingface.co/nathom/ntuple-2048/resolve/main/tuplenet_4M_lr.bin';
-    init(wasm_path).then(() => {
+    const solver = new Solver;
+    init(wasm_path).then(() => solver.init('./solver_worker.js')).then(() => {
         // let weights_promise = downloadFile(weights_url);
 
         // Wait till the browser is ready to render the game (avoids glitches)
         window.requestAnimationFrame(function() {
-            new GameManager(4, weights_url);
+            new GameManager(4, weights_url, solver);
         });
     });
 }
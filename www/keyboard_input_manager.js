@@ -81,6 +81,12 @@ export default class KeyboardInputManager {
             if (!modifiers && event.which === 82) {
                 this.restart.call(this, event);
             }
+
+            // U key takes back the last move
+            if (!modifiers && event.which === 85) {
+                event.preventDefault();
+                this.emit('undo');
+            }
         });
 
         // Respond to button presses
@@ -0,0 +1,16 @@
+# Serve output/ with the headers that let the page use SharedArrayBuffer.
+import http.server
+import sys
+
+
+class IsolatedHandler(http.server.SimpleHTTPRequestHandler):
+    def end_headers(self):
+        self.send_header("Cross-Origin-Opener-Policy", "same-origin")
+        self.send_header("Cross-Origin-Embedder-Policy", "require-corp")
+        super().end_headers()
+
+
+if __name__ == "__main__":
+    port = int(sys.argv[1]) if len(sys.argv) > 1 else 8000
+    handler = lambda *a, **kw: IsolatedHandler(*a, directory="output", **kw)
+    http.server.ThreadingHTTPServer(("", port), handler).serve_forever()
@@ -0,0 +1,77 @@
+import * as wasm from 'solve2048';
+
+/**
+ * Whether the page may share memory with workers, which the threaded build
+ * needs. Browsers only allow it on cross-origin isolated pages.
+ */
+export function threadsAvailable()
+{
+    return typeof SharedArrayBuffer !== 'undefined' &&
+        self.crossOriginIsolated === true;
+}
+
+/**
+ * Solver. Runs the search players on a worker with a thread pool when the
+ * page allows it, and on the page's own thread otherwise.
+ */
+export default class Solver {
+    constructor()
+    {
+        this.worker = null;
+        this.pending = new Map;
+        this.nextId = 0;
+        this.threads = 1;
+    }
+
+    // start the threaded worker at workerPath if possible
+    async init(workerPath)
+    {
+        if (!threadsAvailable()) {
+            console.info('SharedArrayBuffer unavailable, solving on one thread');
+            return;
+        }
+        const worker = new Worker(workerPath, {type: 'module'});
+        const threads = await new Promise(resolve => {
+            worker.onmessage = e => resolve(e.data.threads);
+            worker.onerror = () => resolve(0);
+            worker.postMessage({threads: navigator.hardwareConcurrency});
+        });
+        if (!threads) {
+            console.warn('Solver worker failed to start, solving on one thread');
+            worker.terminate();
+            return;
+        }
+        worker.onmessage = e => this.resolve(e.data);
+        this.worker = worker;
+        this.threads = threads;
+    }
+
+    // the move (0-3, or -1 when the game is over) that solver
+    // ('expectimax' or 'monte_carlo') picks for the exponents in arr
+    bestMove(solver, arr)
+    {
+        if (this.worker === null) {
+            try {
+                return Promise.resolve(wasm[solver](arr));
+            } catch (err) {
+                return Promise.reject(err);
+            }
+        }
+        const id = this.nextId++;
+        return new Promise((resolve, reject) => {
+            this.pending.set(id, {resolve, reject});
+            this.worker.postMessage({id, solver, arr});
+        });
+    }
+
+    resolve({id, move, error})
+    {
+        const {resolve, reject} = this.pending.get(id);
+        this.pending.delete(id);
+        if (error === undefined) {
+            resolve(move);
+        } else {
+            reject(new Error(error));
+        }
+    }
+}
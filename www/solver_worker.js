@@ -0,0 +1,30 @@
+// Module worker for the threaded build, copied unbundled into output/ next to
+// pkg-threads. The page only starts it when SharedArrayBuffer is available.
+import init, {
+    initThreadPool,
+    solver_threads,
+    expectimax,
+    monte_carlo
+} from './pkg-threads/solve2048.js';
+
+const solvers = {expectimax, monte_carlo};
+
+self.onmessage = async ({data}) => {
+    if (data.threads !== undefined) {
+        try {
+            await init();
+            await initThreadPool(data.threads);
+            self.postMessage({threads: solver_threads()});
+        } catch (err) {
+            console.error(err);
+            self.postMessage({threads: 0});
+        }
+        return;
+    }
+    const {id, solver, arr} = data;
+    try {
+        self.postMessage({id, move: solvers[solver](arr)});
+    } catch (err) {
+        self.postMessage({id, error: String(err)});
+    }
+};